@@ -1,19 +1,38 @@
 use std::fs::File;
-use std::io::{BufReader, self};
+use std::borrow::Cow;
+use std::io::{BufReader, Write, self};
 use std::{error::Error, io::BufRead};
 use std::ops::Range;
-use clap::{Parser, command, crate_authors, crate_version, ArgGroup};
+use clap::{Parser, ValueEnum, crate_authors, crate_version, ArgAction, ArgGroup};
 use csv::StringRecord;
 use regex::{Regex, Match};
+use regex::bytes::Regex as BytesRegex;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 pub type MyResult<T> = Result<T, Box<dyn Error>>;
 pub type PositionList = Vec<Range<usize>>;
 
+/// End of a range like `5-` which extends to the end of each line
+pub const OPEN_END: usize = usize::MAX;
+
 #[derive(Debug)]
 pub enum Extract {
     Fields(PositionList),
     Bytes(PositionList),
     Chars(PositionList),
+    FieldNames(Vec<String>),
+    FixedWidth(PositionList),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Tsv,
+    Csv,
+    /// A JSON array with one object per record
+    Json,
+    /// One JSON object per line
+    Jsonl,
 }
 
 #[derive(Debug, Parser)]
@@ -24,7 +43,8 @@ pub enum Extract {
 )]
 #[command(group(
     ArgGroup::new("mode")
-        .args(["field_positions","byte_positions", "char_positions"])
+        .args(["field_positions","byte_positions", "char_positions", "field_names",
+            "field_widths", "columns"])
 ))]
 struct ConfigArgs {
     #[arg(
@@ -47,6 +67,7 @@ struct ConfigArgs {
         short = 'f',
         long = "fields",
         value_name = "FIELDS",
+        allow_hyphen_values = true,
         help = "Selected fields",
     )]
     field_positions: Option<String>,
@@ -55,6 +76,7 @@ struct ConfigArgs {
         short = 'b',
         long = "bytes",
         value_name = "BYTES",
+        allow_hyphen_values = true,
         help = "Selected bytes",
     )]
     byte_positions: Option<String>,
@@ -63,9 +85,156 @@ struct ConfigArgs {
         short = 'c',
         long = "chars",
         value_name = "CHARS",
+        allow_hyphen_values = true,
         help = "Selected characters",
     )]
     char_positions: Option<String>,
+
+    #[arg(
+        short = 'F',
+        long = "field-names",
+        value_name = "NAMES",
+        help = "Selected fields by header name, e.g. name,email or id-email",
+    )]
+    field_names: Option<String>,
+
+    #[arg(
+        long = "no-header",
+        action = ArgAction::SetTrue,
+        conflicts_with = "field_names",
+        help = "Treat the first line as data instead of a header",
+    )]
+    no_header: bool,
+
+    #[arg(
+        long = "complement",
+        action = ArgAction::SetTrue,
+        help = "Complement the set of selected fields, bytes, characters or columns",
+    )]
+    complement: bool,
+
+    #[arg(
+        long = "output-delimiter",
+        value_name = "STRING",
+        help = "Use STRING as the output delimiter for fields (default: input delimiter)",
+    )]
+    output_delimiter: Option<String>,
+
+    #[arg(
+        short = 's',
+        long = "only-delimited",
+        action = ArgAction::SetTrue,
+        help = "Do not print lines not containing delimiters",
+    )]
+    only_delimited: bool,
+
+    #[arg(
+        long = "csv",
+        action = ArgAction::SetTrue,
+        help = "Parse fields as RFC 4180 CSV and quote output fields as needed",
+    )]
+    csv: bool,
+
+    #[arg(
+        long = "delim-regex",
+        value_name = "PATTERN",
+        conflicts_with_all = ["delimiter", "whitespace", "csv"],
+        help = "Split fields at every match of PATTERN",
+    )]
+    delimiter_regex: Option<String>,
+
+    #[arg(
+        short = 'w',
+        long = "whitespace",
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["delimiter", "csv"],
+        help = "Split fields at runs of spaces and tabs, ignoring leading and trailing ones",
+    )]
+    whitespace: bool,
+
+    #[arg(
+        long = "graphemes",
+        action = ArgAction::SetTrue,
+        conflicts_with = "width",
+        help = "Count --chars in extended grapheme clusters",
+    )]
+    graphemes: bool,
+
+    #[arg(
+        long = "width",
+        action = ArgAction::SetTrue,
+        help = "Count --chars in terminal display columns",
+    )]
+    width: bool,
+
+    #[arg(
+        short = 'n',
+        action = ArgAction::SetTrue,
+        help = "With --bytes, do not split multibyte characters",
+    )]
+    no_split: bool,
+
+    #[arg(
+        long = "raw",
+        action = ArgAction::SetTrue,
+        help = "With --bytes, write the selected bytes unchanged",
+    )]
+    raw: bool,
+
+    #[arg(
+        long = "posix-order",
+        action = ArgAction::SetTrue,
+        overrides_with = "reorder",
+        help = "Output selections in input order, merging overlapping ranges",
+    )]
+    posix_order: bool,
+
+    #[arg(
+        long = "reorder",
+        action = ArgAction::SetTrue,
+        overrides_with = "posix_order",
+        help = "Output selections in the given order, repeating them as listed (default)",
+    )]
+    reorder: bool,
+
+    #[arg(
+        long = "widths",
+        visible_alias = "field-widths",
+        value_name = "WIDTHS",
+        help = "Split lines into fixed-width fields of the given widths, e.g. 5,10,3",
+    )]
+    field_widths: Option<String>,
+
+    #[arg(
+        long = "columns",
+        value_name = "COLUMNS",
+        allow_hyphen_values = true,
+        help = "Split lines into fixed-width fields at the given columns, e.g. 1-5,6-15",
+    )]
+    columns: Option<String>,
+
+    #[arg(
+        long = "trim",
+        action = ArgAction::SetTrue,
+        help = "Trim the padding around fixed-width fields",
+    )]
+    trim: bool,
+
+    #[arg(
+        long = "output-format",
+        value_name = "FORMAT",
+        value_enum,
+        help = "Output format for --fields and fixed-width fields; JSON objects use the header names as keys",
+    )]
+    output_format: Option<OutputFormat>,
+}
+
+/// Unit of the positions given with --chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharUnit {
+    Char,
+    Grapheme,
+    Column,
 }
 
 #[derive(Debug)]
@@ -73,6 +242,19 @@ pub struct Config {
     files: Vec<String>,
     delimiter: u8,
     extract: Extract,
+    complement: bool,
+    output_delimiter: String,
+    only_delimited: bool,
+    has_header: bool,
+    csv: bool,
+    delimiter_regex: Option<BytesRegex>,
+    whitespace: bool,
+    char_unit: CharUnit,
+    no_split: bool,
+    raw: bool,
+    posix_order: bool,
+    trim: bool,
+    output_format: Option<OutputFormat>,
 }
 
 pub fn get_config() -> MyResult<Config> {
@@ -82,20 +264,54 @@ pub fn get_config() -> MyResult<Config> {
 
 fn config_args_into_config(args: ConfigArgs) -> MyResult<Config> {
 
-    let all_positions = vec![
+    let all_positions = [
         &args.field_positions,
         &args.byte_positions,
         &args.char_positions,
+        &args.field_names,
+        &args.field_widths,
+        &args.columns,
     ];
 
     if all_positions.iter().all(|pos_opt| { pos_opt.is_none() }) {
         return Err(Box::<dyn Error>::from("Must have --fields, --bytes, or --chars"));
     }
 
+    if (args.graphemes || args.width) && args.char_positions.is_none() {
+        return Err(Box::<dyn Error>::from("--graphemes and --width require --chars"));
+    }
+
+    if (args.no_split || args.raw) && args.byte_positions.is_none() {
+        return Err(Box::<dyn Error>::from("-n and --raw require --bytes"));
+    }
+
+    if args.output_format.is_some() && (args.byte_positions.is_some() || args.char_positions.is_some()) {
+        return Err(Box::<dyn Error>::from("--output-format requires --fields, -F or fixed-width fields"));
+    }
+
     Ok(Config {
         files: args.files.clone(),
         delimiter: get_delimiter(&args)?,
-        extract: get_extract(&args)? })
+        extract: get_extract(&args)?,
+        complement: args.complement,
+        output_delimiter: args.output_delimiter.clone().unwrap_or(args.delimiter.clone()),
+        only_delimited: args.only_delimited,
+        has_header: !args.no_header,
+        csv: args.csv,
+        delimiter_regex: get_delimiter_regex(&args)?,
+        whitespace: args.whitespace,
+        char_unit: if args.graphemes {
+            CharUnit::Grapheme
+        } else if args.width {
+            CharUnit::Column
+        } else {
+            CharUnit::Char
+        },
+        no_split: args.no_split,
+        raw: args.raw,
+        posix_order: args.posix_order && !args.reorder,
+        trim: args.trim,
+        output_format: args.output_format })
 }
 
 fn get_delimiter(args: &ConfigArgs) -> MyResult<u8> {
@@ -110,6 +326,22 @@ fn get_delimiter(args: &ConfigArgs) -> MyResult<u8> {
     Ok(bytes[0])
 }
 
+fn get_delimiter_regex(args: &ConfigArgs) -> MyResult<Option<BytesRegex>> {
+
+    let pattern = if args.whitespace {
+        "[ \t]+"
+    } else if let Some(pattern) = &args.delimiter_regex {
+        pattern.as_str()
+    } else {
+        return Ok(None);
+    };
+
+    match BytesRegex::new(pattern) {
+        Ok(re) => Ok(Some(re)),
+        Err(_) => Err(Box::<dyn Error>::from(format!("--delim-regex \"{}\" is not a valid regex", pattern))),
+    }
+}
+
 fn get_extract(args: &ConfigArgs) -> MyResult<Extract> {
 
     if let Some(positions) = &args.field_positions {
@@ -118,12 +350,85 @@ fn get_extract(args: &ConfigArgs) -> MyResult<Extract> {
         Ok(Extract::Bytes(parse_pos(positions)?))
     } else if let Some(positions) = &args.char_positions {
         Ok(Extract::Chars(parse_pos(positions)?))
+    } else if let Some(names) = &args.field_names {
+        Ok(Extract::FieldNames(parse_names(names)?))
+    } else if let Some(widths) = &args.field_widths {
+        Ok(Extract::FixedWidth(parse_widths(widths)?))
+    } else if let Some(columns) = &args.columns {
+        Ok(Extract::FixedWidth(parse_pos(columns)?))
     } else {
         Err(Box::<dyn Error>::from("No positions were specified".to_string()))
     }
 
 }
 
+/// Converts field widths like `5,10,3` into the column ranges `1-5,6-15,16-18`
+fn parse_widths(widths: &str) -> MyResult<PositionList> {
+
+    let mut start = 0;
+
+    widths
+        .split(',')
+        .map(|width| {
+            match width.parse::<usize>() {
+                Ok(w) if w > 0 && width.bytes().all(|b| { b.is_ascii_digit() }) => {
+                    let rng = start..(start + w);
+                    start += w;
+                    Ok(rng)
+                },
+                _ => create_range_error(&format!("illegal width: \"{}\"", width)),
+            }
+        })
+        .collect()
+}
+
+fn parse_names(names: &str) -> MyResult<Vec<String>> {
+    names
+        .split(',')
+        .map(|name| {
+            if name.is_empty() {
+                Err(Box::<dyn Error>::from(format!("illegal list value: \"{}\"", names)))
+            } else {
+                Ok(name.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Resolves field names and name ranges like `id-email` against a header
+fn resolve_names(names: &[String], header: &StringRecord) -> MyResult<PositionList> {
+    names
+        .iter()
+        .map(|name| { name_to_range(name, header) })
+        .collect()
+}
+
+fn name_to_range(name: &str, header: &StringRecord) -> MyResult<Range<usize>> {
+
+    let index_of = |name: &str| { header.iter().position(|field| { field == name }) };
+
+    if let Some(idx) = index_of(name) {
+        return Ok(idx..(idx + 1));
+    }
+
+    // Names may contain dashes themselves, so try every split point
+    for (dash, _) in name.match_indices('-') {
+        let (first, last) = (&name[..dash], &name[(dash + 1)..]);
+        if let (Some(start), Some(end)) = (index_of(first), index_of(last)) {
+            return if start <= end {
+                Ok(start..(end + 1))
+            } else {
+                create_range_error(
+                    &format!("Column \"{}\" must come before column \"{}\"", first, last))
+            };
+        }
+    }
+
+    let available = header.iter().collect::<Vec<&str>>().join(", ");
+    create_range_error(
+        &format!("unknown column \"{}\", available columns: {}", name, available))
+}
+
 fn parse_pos(range: &str) -> MyResult<PositionList> {
     range
         .split(',')
@@ -133,36 +438,45 @@ fn parse_pos(range: &str) -> MyResult<PositionList> {
 
 fn interval_to_range(interval: &str) -> MyResult<Range<usize>> {
 
-    let re = Regex::new("^(\\d+)(-(\\d+))?$")?;
+    let re = Regex::new("^(\\d*)(-(\\d*))?$")?;
 
     let captures= match re.captures(interval) {
         Some(caps) => caps,
         None => return create_interval_error(interval),
     };
 
-    let start = match captures.get(1) {
-        Some(m) => parse_index(&m)?,
-        None => return create_interval_error(interval),
-    };
+    let start = captures
+        .get(1)
+        .filter(|m| { !m.as_str().is_empty() })
+        .map(|m| { parse_index(&m) })
+        .transpose()?;
 
-    if start < 1 {
-        return create_interval_error(&format!("{}", start));
+    if start == Some(0) {
+        return create_interval_error("0");
     }
 
+    // Without a dash this is a single position, which must be given
+    let Some(dash) = captures.get(2) else {
+        return match start {
+            Some(start) => Ok((start - 1)..start),
+            None => create_interval_error(interval),
+        };
+    };
+
     let end = captures
         .get(3)
+        .filter(|m| { !m.as_str().is_empty() })
         .map(|m| { parse_index(&m) })
         .transpose()?;
 
-    if let Some(end) = end {
-        if start < end {
-            Ok((start - 1)..end)
-        } else {
-            create_range_error(
-                &format!("First number in range ({}) must be lower than second number ({})", start, end))
-        }
-    } else {
-        Ok((start - 1)..start)
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => Ok((start - 1)..end),
+        (Some(start), Some(end)) => create_range_error(
+            &format!("First number in range ({}) must be lower than second number ({})", start, end)),
+        (None, Some(0)) => create_interval_error(dash.as_str()),
+        (None, Some(end)) => Ok(0..end),
+        (Some(start), None) => Ok((start - 1)..OPEN_END),
+        (None, None) => Ok(0..OPEN_END),
     }
 }
 
@@ -183,14 +497,30 @@ fn create_range_error(message: &str) -> MyResult<Range<usize>> {
 
 pub fn run(config: Config) -> MyResult<()> {
 
+    // A single printer for all inputs, so that JSON output is one array
+    let mut printer = match config.extract {
+        Extract::Bytes(_) | Extract::Chars(_) => None,
+        _ => config.output_format.map(|format| { RecordPrinter::new(format, &config) }),
+    };
+    let mut num_failed = 0;
     for filename in &config.files {
-        match open(filename) {
-            Ok(mut file) => run_file(&mut file, &config),
-            Err(e) => eprintln!("{}: {}", filename, e),
+        let result = open(filename).and_then(|mut file| { run_file(&mut file, &config, &mut printer) });
+        if let Err(e) = result {
+            eprintln!("{}: {}", filename, e);
+            num_failed += 1;
         }
     }
 
-    Ok(())
+    if let Some(printer) = &printer {
+        printer.finish(&mut io::stdout().lock())?;
+    }
+
+    // Keep going past bad inputs like cut, but report the failure at the end
+    match num_failed {
+        0 => Ok(()),
+        _ => Err(Box::<dyn Error>::from(format!("{} of {} inputs could not be read",
+            num_failed, config.files.len()))),
+    }
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
@@ -200,36 +530,434 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-fn run_file(file: &mut Box<dyn BufRead>, config: &Config) {
-    if let Extract::Fields(positions) = &config.extract {
-        extract_fields_from_file(file, positions, config);
+fn run_file(file: &mut Box<dyn BufRead>, config: &Config,
+    printer: &mut Option<RecordPrinter>) -> MyResult<()> {
+
+    match &config.extract {
+        Extract::Fields(_) | Extract::FieldNames(_) => extract_fields_from_file(file, config, printer),
+        Extract::Bytes(positions) if config.raw => extract_raw_bytes_from_file(file, positions, config),
+        Extract::FixedWidth(columns) => match printer {
+            Some(printer) => print_fixed_width_records(file, columns, config, printer),
+            None => run_lines(file, config),
+        },
+        _ => run_lines(file, config),
+    }
+}
+
+fn run_lines(file: &mut Box<dyn BufRead>, config: &Config) -> MyResult<()> {
+    for (line_num, line) in file.lines().enumerate() {
+        let line = line.map_err(|e| { line_error(line_num + 1, e) })?;
+        run_line(&line, config);
+    }
+    Ok(())
+}
+
+/// Writes the fixed-width fields of each line in the given --output-format
+fn print_fixed_width_records(file: &mut Box<dyn BufRead>, columns: &[Range<usize>],
+    config: &Config, printer: &mut RecordPrinter) -> MyResult<()> {
+
+    let mut out = io::stdout().lock();
+    let mut is_header = config.has_header;
+
+    for (line_num, line) in file.lines().enumerate() {
+        let line = line.map_err(|e| { line_error(line_num + 1, e) })?;
+        let fields: Vec<String> = fixed_width_fields(&line, columns, config)
+            .into_iter()
+            .map(String::from)
+            .collect();
+        printer.print_fields(&mut out, fields, is_header)?;
+        is_header = false;
+    }
+
+    Ok(())
+}
+
+/// Reads the next line including its newline into `buf`, returning the
+/// number of bytes read
+fn read_line(file: &mut Box<dyn BufRead>, buf: &mut Vec<u8>, line_num: usize) -> MyResult<usize> {
+    buf.clear();
+    file.read_until(b'\n', buf).map_err(|e| { line_error(line_num, e) })
+}
+
+fn line_error(line_num: usize, error: impl Error) -> Box<dyn Error> {
+    Box::<dyn Error>::from(format!("line {}: {}", line_num, error))
+}
+
+/// Copies the selected bytes of each line to stdout without any conversion,
+/// so that invalid UTF-8 passes through unchanged.
+fn extract_raw_bytes_from_file(file: &mut Box<dyn BufRead>, positions: &[Range<usize>],
+    config: &Config) -> MyResult<()> {
+
+    let mut out = io::stdout().lock();
+    let mut buf = vec![];
+
+    for line_num in 1.. {
+        if read_line(file, &mut buf, line_num)? == 0 {
+            break;
+        }
+        let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        let line_positions = byte_positions(line, positions, config);
+        for rng in &line_positions {
+            out.write_all(raw_bytes_in_range(line, rng))?;
+        }
+        out.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+fn extract_fields_from_file(file: &mut Box<dyn BufRead>, config: &Config,
+    printer: &mut Option<RecordPrinter>) -> MyResult<()> {
+
+    if config.csv {
+        extract_csv_fields_from_file(file, config, printer)
     } else {
-        file.lines().flatten().for_each(|line| {
-            run_line(&line, config);
-        });
+        extract_raw_fields_from_file(file, config, printer)
+    }
+}
+
+/// Splits each line at every delimiter byte like POSIX cut, without any
+/// special treatment of quotes. Fields are written as raw bytes.
+fn extract_raw_fields_from_file(file: &mut Box<dyn BufRead>, config: &Config,
+    printer: &mut Option<RecordPrinter>) -> MyResult<()> {
+
+    let mut out = io::stdout().lock();
+    let mut positions = match &config.extract {
+        Extract::Fields(positions) => Some(positions.clone()),
+        _ => None,
+    };
+    let mut buf = vec![];
+    let mut is_header = config.has_header;
+
+    for line_num in 1.. {
+        if read_line(file, &mut buf, line_num)? == 0 {
+            break;
+        }
+        let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        let fields = split_line(line, config);
+
+        // Field names are resolved against the first line
+        let line_positions = match (&positions, &config.extract) {
+            (Some(positions), _) => positions,
+            (None, Extract::FieldNames(names)) => {
+                let header: StringRecord = fields
+                    .iter()
+                    .map(|field| { String::from_utf8_lossy(field) })
+                    .collect();
+                positions.insert(resolve_names(names, &header)?)
+            },
+            _ => return Ok(()),
+        };
+
+        match printer {
+            Some(printer) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|field| { String::from_utf8_lossy(field).into_owned() })
+                    .collect();
+                if is_header {
+                    printer.print_header(&mut out, fields, line_positions)?;
+                } else {
+                    printer.print_record(&mut out, &fields, line_positions)?;
+                }
+            },
+            None => print_raw_fields(&mut out, line, &fields, line_positions, config)?,
+        }
+        is_header = false;
     }
+
+    Ok(())
 }
 
-fn extract_fields_from_file(file: &mut Box<dyn BufRead>, positions: &Vec<Range<usize>>,
-    config: &Config) {
+fn split_line<'a>(line: &'a [u8], config: &Config) -> Vec<&'a [u8]> {
+    match &config.delimiter_regex {
+        Some(re) if config.whitespace => split_regex_fields(trim_whitespace(line), re),
+        Some(re) => split_regex_fields(line, re),
+        None => split_raw_fields(line, config.delimiter),
+    }
+}
 
-    let mut delim_str = String::new();
-    delim_str.push(config.delimiter as char);
+fn split_raw_fields(line: &[u8], delimiter: u8) -> Vec<&[u8]> {
+    line.split(|&byte| { byte == delimiter }).collect()
+}
+
+fn split_regex_fields<'a>(line: &'a [u8], re: &BytesRegex) -> Vec<&'a [u8]> {
+    re.split(line).collect()
+}
+
+fn trim_whitespace(line: &[u8]) -> &[u8] {
+    let is_blank = |byte: &u8| { *byte == b' ' || *byte == b'\t' };
+    let start = line.iter().position(|byte| { !is_blank(byte) }).unwrap_or(line.len());
+    let end = line.iter().rposition(|byte| { !is_blank(byte) }).map_or(start, |pos| { pos + 1 });
+    &line[start..end]
+}
+
+fn print_raw_fields(out: &mut impl Write, line: &[u8], fields: &[&[u8]],
+    positions: &[Range<usize>], config: &Config) -> MyResult<()> {
+
+    if fields.len() < 2 {
+        if !config.only_delimited {
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+        }
+        return Ok(());
+    }
+
+    let record_positions = select_positions(positions, fields.len(), config);
+    let selected = extract_raw_fields(fields, &record_positions);
+    out.write_all(&selected.join(config.output_delimiter.as_bytes()))?;
+    out.write_all(b"\n")?;
+
+    Ok(())
+}
 
+fn extract_raw_fields<'a>(fields: &[&'a [u8]], field_positions: &[Range<usize>]) -> Vec<&'a [u8]> {
+    field_positions
+        .iter()
+        .flat_map(|rng| {
+            let end = rng.end.min(fields.len());
+            fields[rng.start.min(end)..end].iter().copied()
+        })
+        .collect()
+}
+
+fn extract_csv_fields_from_file(file: &mut Box<dyn BufRead>, config: &Config,
+    printer: &mut Option<RecordPrinter>) -> MyResult<()> {
+
+    // Lines without a delimiter yield records of a different length, so
+    // the reader must accept ragged rows to be able to tell them apart
     let mut reader = csv::ReaderBuilder::new()
         .delimiter(config.delimiter)
+        .flexible(true)
+        .has_headers(config.has_header)
         .from_reader(file);
 
-    if let Ok(header) = reader.headers() {
-        let header_fields = extract_fields(header, positions);
-        println!("{}", header_fields.join(&delim_str));
+    let mut out = io::stdout().lock();
+
+    let positions = if config.has_header {
+        let header = reader.headers()?.clone();
+        if header.is_empty() {
+            return Ok(());
+        }
+        let positions = match &config.extract {
+            Extract::FieldNames(names) => resolve_names(names, &header)?,
+            Extract::Fields(positions) => positions.clone(),
+            _ => return Ok(()),
+        };
+        match printer {
+            Some(printer) => printer.print_header(&mut out, record_to_vec(&header), &positions)?,
+            None => print_record(&header, &positions, config),
+        }
+        positions
+    } else {
+        match &config.extract {
+            Extract::Fields(positions) => positions.clone(),
+            _ => return Ok(()),
+        }
+    };
+
+    // Parse errors already name the offending record and line
+    for record in reader.records() {
+        let record = record?;
+        match printer {
+            Some(printer) => printer.print_record(&mut out, &record_to_vec(&record), &positions)?,
+            None => print_record(&record, &positions, config),
+        }
     }
 
-    for record in reader.records().flatten() {
-        let fields = extract_fields(&record, positions);
-        println!("{}", fields.join(&delim_str));
+    Ok(())
+}
+
+fn record_to_vec(record: &StringRecord) -> Vec<String> {
+    record.iter().map(String::from).collect()
+}
+
+/// Writes the selected fields of each record in the given --output-format
+struct RecordPrinter<'a> {
+    format: OutputFormat,
+    config: &'a Config,
+    header: Option<Vec<String>>,
+    num_records: usize,
+}
+
+impl<'a> RecordPrinter<'a> {
+
+    fn new(format: OutputFormat, config: &'a Config) -> RecordPrinter<'a> {
+        RecordPrinter { format, config, header: None, num_records: 0 }
     }
 
+    /// TSV and CSV print the header like any other record, whereas JSON
+    /// uses it for the keys of the following records
+    fn print_header(&mut self, out: &mut impl Write, header: Vec<String>,
+        positions: &[Range<usize>]) -> MyResult<()> {
+
+        match self.format {
+            OutputFormat::Tsv | OutputFormat::Csv => self.print_record(out, &header, positions),
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                self.header = Some(header);
+                Ok(())
+            },
+        }
+    }
+
+    fn print_record(&mut self, out: &mut impl Write, fields: &[String],
+        positions: &[Range<usize>]) -> MyResult<()> {
+
+        // A line without the delimiter is not a record, so JSON skips it
+        // while TSV and CSV print it unchanged like cut
+        if fields.len() < 2 {
+            let is_json = matches!(self.format, OutputFormat::Json | OutputFormat::Jsonl);
+            if self.config.only_delimited || is_json {
+                return Ok(());
+            }
+            return self.write_fields(out, fields, &[0]);
+        }
+
+        let record_positions = select_positions(positions, fields.len(), self.config);
+        let indices: Vec<usize> = record_positions
+            .iter()
+            .flat_map(|rng| { rng.start.min(fields.len())..rng.end.min(fields.len()) })
+            .collect();
+        self.write_fields(out, fields, &indices)
+    }
+
+    /// Prints fields that are already selected, like fixed-width fields,
+    /// regardless of their number
+    fn print_fields(&mut self, out: &mut impl Write, fields: Vec<String>,
+        is_header: bool) -> MyResult<()> {
+
+        if is_header && matches!(self.format, OutputFormat::Json | OutputFormat::Jsonl) {
+            self.header = Some(fields);
+            return Ok(());
+        }
+
+        let indices: Vec<usize> = (0..fields.len()).collect();
+        self.write_fields(out, &fields, &indices)
+    }
+
+    fn write_fields(&mut self, out: &mut impl Write, fields: &[String],
+        indices: &[usize]) -> MyResult<()> {
+
+        match self.format {
+            OutputFormat::Tsv | OutputFormat::Csv => {
+                let separator = if self.format == OutputFormat::Tsv { "\t" } else { "," };
+                let line = indices
+                    .iter()
+                    .map(|&idx| { self.format_field(&fields[idx]) })
+                    .collect::<Vec<Cow<str>>>()
+                    .join(separator);
+                writeln!(out, "{}", line)?;
+            },
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let value = self.to_json(fields, indices)?;
+                if self.format == OutputFormat::Jsonl {
+                    writeln!(out, "{}", value)?;
+                } else if self.num_records == 0 {
+                    write!(out, "[\n  {}", value)?;
+                } else {
+                    write!(out, ",\n  {}", value)?;
+                }
+            },
+        }
+
+        self.num_records += 1;
+        Ok(())
+    }
+
+    fn finish(&self, out: &mut impl Write) -> MyResult<()> {
+        if self.format == OutputFormat::Json {
+            if self.num_records == 0 {
+                writeln!(out, "[]")?;
+            } else {
+                writeln!(out, "\n]")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn format_field<'f>(&self, field: &'f str) -> Cow<'f, str> {
+        match self.format {
+            OutputFormat::Csv => quote_field(field, ","),
+            _ => escape_tsv_field(field),
+        }
+    }
+
+    /// Records become objects keyed by the header names, or arrays if there
+    /// is no header. Fields beyond the header are keyed by their number.
+    fn to_json(&self, fields: &[String], indices: &[usize]) -> MyResult<String> {
+        let values = indices
+            .iter()
+            .map(|&idx| {
+                let value = serde_json::to_string(&fields[idx])?;
+                Ok(match &self.header {
+                    Some(header) => {
+                        let key = header
+                            .get(idx)
+                            .cloned()
+                            .unwrap_or_else(|| { (idx + 1).to_string() });
+                        format!("{}:{}", serde_json::to_string(&key)?, value)
+                    },
+                    None => value,
+                })
+            })
+            .collect::<MyResult<Vec<String>>>()?
+            .join(",");
+
+        if self.header.is_some() {
+            Ok(format!("{{{}}}", values))
+        } else {
+            Ok(format!("[{}]", values))
+        }
+    }
+}
+
+/// Escapes tabs, line breaks and backslashes, which TSV fields cannot contain
+fn escape_tsv_field(field: &str) -> Cow<'_, str> {
+    if field.contains(['\t', '\n', '\r', '\\']) {
+        Cow::Owned(field
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+fn print_record(record: &StringRecord, positions: &[Range<usize>], config: &Config) {
+
+    // A single field means that the line does not contain the delimiter. Like
+    // cut, such lines are printed unchanged unless --only-delimited is set.
+    if !is_delimited(record) {
+        if !config.only_delimited {
+            let field = record.get(0).unwrap_or_default();
+            println!("{}", quote_field(field, &config.output_delimiter));
+        }
+        return;
+    }
+
+    let record_positions = select_positions(positions, record.len(), config);
+    let fields: Vec<String> = extract_fields(record, &record_positions)
+        .iter()
+        .map(|field| { quote_field(field, &config.output_delimiter).into_owned() })
+        .collect();
+    println!("{}", fields.join(&config.output_delimiter));
+}
+
+/// Quotes a CSV field if it contains the delimiter, a quote or a line break
+fn quote_field<'a>(field: &'a str, delimiter: &str) -> Cow<'a, str> {
+    let needs_quotes = (!delimiter.is_empty() && field.contains(delimiter))
+        || field.contains(['"', '\n', '\r']);
+
+    if needs_quotes {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+fn is_delimited(record: &StringRecord) -> bool {
+    record.len() > 1
 }
 
 fn extract_fields(record: &StringRecord, field_positions: &[Range<usize>]) -> Vec<String> {
@@ -246,7 +974,7 @@ fn fields_in_range(record: &StringRecord, range: &Range<usize>) -> Vec<String> {
 
     let mut ret: Vec<String> = vec![];
 
-    for i in range.start..range.end {
+    for i in range.start..range.end.min(record.len()) {
         if let Some(field) = record.get(i) {
             ret.push(field.to_string());
         }
@@ -255,17 +983,147 @@ fn fields_in_range(record: &StringRecord, range: &Range<usize>) -> Vec<String> {
     ret
 }
 
+/// Returns the positions to extract from a line with `len` fields, bytes or
+/// characters, taking `--complement` and `--posix-order` into account.
+fn select_positions(positions: &[Range<usize>], len: usize, config: &Config) -> PositionList {
+    if config.complement {
+        complement(positions, len)
+    } else if config.posix_order {
+        merge_positions(positions)
+    } else {
+        positions.to_vec()
+    }
+}
+
+/// Sorts the ranges and merges overlapping or adjacent ones, so that every
+/// position is selected at most once and in input order like POSIX cut does
+fn merge_positions(positions: &[Range<usize>]) -> PositionList {
+
+    let mut sorted: PositionList = positions
+        .iter()
+        .filter(|rng| { rng.start < rng.end })
+        .cloned()
+        .collect();
+    sorted.sort_by_key(|rng| { rng.start });
+
+    let mut ret: PositionList = vec![];
+
+    for rng in sorted {
+        match ret.last_mut() {
+            Some(last) if rng.start <= last.end => last.end = last.end.max(rng.end),
+            _ => ret.push(rng),
+        }
+    }
+
+    ret
+}
+
+fn complement(positions: &[Range<usize>], len: usize) -> PositionList {
+
+    let mut selected = vec![false; len];
+    for rng in positions {
+        let end = rng.end.min(len);
+        let start = rng.start.min(end);
+        selected[start..end].fill(true);
+    }
+
+    let mut ret = vec![];
+    let mut start = None;
+
+    for (idx, &is_selected) in selected.iter().enumerate() {
+        match (is_selected, start) {
+            (false, None) => start = Some(idx),
+            (true, Some(s)) => {
+                ret.push(s..idx);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(s) = start {
+        ret.push(s..len);
+    }
+
+    ret
+}
+
 fn run_line(line: &str, config: &Config) {
 
     let extracted = match &config.extract {
-        Extract::Chars(positions) => extract_chars(line, positions),
-        Extract::Bytes(positions) => extract_bytes(line, positions),
+        Extract::Chars(positions) => match config.char_unit {
+            CharUnit::Char => {
+                let positions = select_positions(positions, line.chars().count(), config);
+                extract_chars(line, &positions)
+            },
+            CharUnit::Grapheme => {
+                let positions = select_positions(positions, line.graphemes(true).count(), config);
+                extract_graphemes(line, &positions)
+            },
+            CharUnit::Column => {
+                let positions = select_positions(positions, line.width(), config);
+                extract_columns(line, &positions)
+            },
+        },
+        Extract::Bytes(positions) => {
+            let positions = byte_positions(line.as_bytes(), positions, config);
+            extract_bytes(line, &positions)
+        },
+        Extract::FixedWidth(columns) => {
+            fixed_width_fields(line, columns, config)
+                .iter()
+                .map(|field| {
+                    if config.csv {
+                        quote_field(field, &config.output_delimiter)
+                    } else {
+                        Cow::Borrowed(*field)
+                    }
+                })
+                .collect::<Vec<Cow<str>>>()
+                .join(&config.output_delimiter)
+        },
         _ => return,
     };
 
     println!("{}", extracted);
 }
 
+/// Cuts a line into the fixed-width fields at the given columns, or with
+/// `--complement` into the stretches of the line between them
+fn fixed_width_fields<'a>(line: &'a str, columns: &[Range<usize>], config: &Config) -> Vec<&'a str> {
+    if config.complement {
+        extract_fixed_width(line, &complement(columns, line.chars().count()), config.trim)
+    } else {
+        extract_fixed_width(line, columns, config.trim)
+    }
+}
+
+/// Cuts a line into fixed-width fields. Lines shorter than the last column
+/// yield partial or empty fields.
+fn extract_fixed_width<'a>(line: &'a str, columns: &[Range<usize>], trim: bool) -> Vec<&'a str> {
+
+    let offsets: Vec<usize> = line
+        .char_indices()
+        .map(|(offset, _)| { offset })
+        .chain(std::iter::once(line.len()))
+        .collect();
+    let num_chars = offsets.len() - 1;
+
+    columns
+        .iter()
+        .map(|rng| {
+            let end = resolve_end(rng, num_chars).min(num_chars);
+            let start = rng.start.min(end);
+            let field = &line[offsets[start]..offsets[end]];
+            if trim {
+                field.trim()
+            } else {
+                field
+            }
+        })
+        .collect()
+}
+
 fn extract_chars(line: &str, char_positions: &[Range<usize>]) -> String {
 
     let chars: Vec<char> = line.chars().collect();
@@ -278,10 +1136,10 @@ fn extract_chars(line: &str, char_positions: &[Range<usize>]) -> String {
 
 }
 
-fn chars_in_range(chars: &Vec<char>, range: &Range<usize>) -> String {
+fn chars_in_range(chars: &[char], range: &Range<usize>) -> String {
     let l = chars.len();
     let s = range.start;
-    let e = range.end;
+    let e = resolve_end(range, l);
 
     if s < e && e <= l {
         let mut ret = String::new();
@@ -294,6 +1152,66 @@ fn chars_in_range(chars: &Vec<char>, range: &Range<usize>) -> String {
     }
 }
 
+fn extract_graphemes(line: &str, grapheme_positions: &[Range<usize>]) -> String {
+
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+
+    grapheme_positions
+        .iter()
+        .map(|rng| { graphemes_in_range(&graphemes, rng) })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+fn graphemes_in_range(graphemes: &[&str], range: &Range<usize>) -> String {
+    let l = graphemes.len();
+    let s = range.start;
+    let e = resolve_end(range, l);
+
+    if s < e && e <= l {
+        graphemes[s..e].concat()
+    } else {
+        "".to_string()
+    }
+}
+
+/// Selects grapheme clusters by display column. A cluster spanning several
+/// columns is selected as a whole if the range contains its first column.
+fn extract_columns(line: &str, column_positions: &[Range<usize>]) -> String {
+
+    let mut column = 0;
+    let graphemes: Vec<(usize, &str)> = line
+        .graphemes(true)
+        .map(|grapheme| {
+            let start = column;
+            column += grapheme.width();
+            (start, grapheme)
+        })
+        .collect();
+
+    column_positions
+        .iter()
+        .map(|rng| {
+            let end = resolve_end(rng, column);
+            graphemes
+                .iter()
+                .filter(|(start, _)| { rng.start <= *start && *start < end })
+                .map(|(_, grapheme)| { *grapheme })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+/// Resolves an open range end against the length of the current line
+fn resolve_end(range: &Range<usize>, len: usize) -> usize {
+    if range.end == OPEN_END {
+        len
+    } else {
+        range.end
+    }
+}
+
 fn extract_bytes(line: &str, byte_positions: &[Range<usize>]) -> String {
 
     let bytes: Vec<u8> = line.bytes().collect();
@@ -306,22 +1224,66 @@ fn extract_bytes(line: &str, byte_positions: &[Range<usize>]) -> String {
 }
 
 fn bytes_in_range(bytes: &[u8], range: &Range<usize>) -> String {
+    String::from_utf8_lossy(raw_bytes_in_range(bytes, range)).to_string()
+}
+
+fn raw_bytes_in_range<'a>(bytes: &'a [u8], range: &Range<usize>) -> &'a [u8] {
     let l = bytes.len();
     let s = range.start;
-    let e = range.end;
+    let e = resolve_end(range, l);
 
     if s < e && e <= l {
-        String::from_utf8_lossy(&bytes[s..e]).to_string()
+        &bytes[s..e]
     } else {
-        "".to_string()
+        &[]
     }
 }
 
+/// Returns the byte positions to extract from a line, taking --complement
+/// and -n into account
+fn byte_positions(bytes: &[u8], positions: &[Range<usize>], config: &Config) -> PositionList {
+    let positions = select_positions(positions, bytes.len(), config);
+    if config.no_split {
+        to_char_boundaries(bytes, &positions)
+    } else {
+        positions
+    }
+}
+
+/// Shrinks byte ranges so that they only contain complete UTF-8 characters:
+/// a start inside a character moves to the next character and an end inside
+/// a character moves back to the start of that character.
+fn to_char_boundaries(bytes: &[u8], byte_positions: &[Range<usize>]) -> PositionList {
+
+    let l = bytes.len();
+    let is_inside_char = |idx: usize| { idx < l && (bytes[idx] & 0xC0) == 0x80 };
+
+    byte_positions
+        .iter()
+        .map(|rng| {
+            let mut s = rng.start;
+            let mut e = resolve_end(rng, l);
+            while is_inside_char(s) {
+                s += 1;
+            }
+            while e > s && is_inside_char(e) {
+                e -= 1;
+            }
+            s..e.max(s)
+        })
+        .collect()
+}
+
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod unit_tests {
     use csv::StringRecord;
 
-    use super::{parse_pos, extract_chars, extract_bytes, extract_fields};
+    use super::{parse_pos, extract_chars, extract_bytes, extract_fields, complement,
+        is_delimited, resolve_names, split_raw_fields, extract_raw_fields, quote_field,
+        split_regex_fields, trim_whitespace, extract_graphemes, extract_columns, to_char_boundaries,
+        merge_positions, parse_widths, extract_fixed_width, escape_tsv_field, PositionList, OPEN_END};
+    use regex::bytes::Regex as BytesRegex;
 
     #[test]
     fn test_parse_pos() {
@@ -383,18 +1345,23 @@ mod unit_tests {
         );
 
         // Wonky ranges
-        let res = parse_pos("-");
-        assert!(res.is_err());
-
         let res = parse_pos(",");
         assert!(res.is_err());
 
         let res = parse_pos("1,");
         assert!(res.is_err());
 
-        let res = parse_pos("1-");
+        let res = parse_pos("--");
         assert!(res.is_err());
 
+        let res = parse_pos("-0");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"-0\"",);
+
+        let res = parse_pos("0-");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"",);
+
         let res = parse_pos("1-1-1");
         assert!(res.is_err());
 
@@ -448,6 +1415,31 @@ mod unit_tests {
         let res = parse_pos("15,19-20");
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), vec![14..15, 18..20]);
+
+        // Open-ended ranges
+        let res = parse_pos("-3");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..3]);
+
+        let res = parse_pos("-1");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..1]);
+
+        let res = parse_pos("5-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![4..OPEN_END]);
+
+        let res = parse_pos("-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..OPEN_END]);
+
+        let res = parse_pos("1-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..OPEN_END]);
+
+        let res = parse_pos("3-,-2");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![2..OPEN_END, 0..2]);
     }
 
     #[test]
@@ -461,6 +1453,8 @@ mod unit_tests {
             extract_chars("ábc", &[0..1, 1..2, 4..5]),
             "áb".to_string()
         );
+        assert_eq!(extract_chars("ábc", &[1..OPEN_END]), "bc".to_string());
+        assert_eq!(extract_chars("ábc", &[3..OPEN_END]), "".to_string());
     }
 
     #[test]
@@ -471,6 +1465,28 @@ mod unit_tests {
         assert_eq!(extract_bytes("ábc", &[0..4]), "ábc".to_string());
         assert_eq!(extract_bytes("ábc", &[3..4, 2..3]), "cb".to_string());
         assert_eq!(extract_bytes("ábc", &[0..2, 5..6]), "á".to_string());
+        assert_eq!(extract_bytes("ábc", &[2..OPEN_END]), "bc".to_string());
+        assert_eq!(extract_bytes("ábc", &[4..OPEN_END]), "".to_string());
+    }
+
+    #[test]
+    fn test_to_char_boundaries() {
+        let bytes = "ábc".as_bytes();
+        assert_eq!(to_char_boundaries(bytes, &[0..1]), vec![0..0]);
+        assert_eq!(to_char_boundaries(bytes, &[0..2]), vec![0..2]);
+        assert_eq!(to_char_boundaries(bytes, &[1..3]), vec![2..3]);
+        assert_eq!(to_char_boundaries(bytes, &[1..OPEN_END]), vec![2..4]);
+        assert_eq!(to_char_boundaries(bytes, &[0..6]), vec![0..6]);
+        assert_eq!(extract_bytes("ábc", &to_char_boundaries(bytes, &[0..1])), "".to_string());
+        assert_eq!(extract_bytes("ábc", &to_char_boundaries(bytes, &[0..3])), "áb".to_string());
+        assert_eq!(
+            extract_bytes("日本", &to_char_boundaries("日本".as_bytes(), &[1..5])),
+            "".to_string()
+        );
+        assert_eq!(
+            extract_bytes("日本", &to_char_boundaries("日本".as_bytes(), &[1..6])),
+            "本".to_string()
+        );
     }
 
     #[test]
@@ -484,6 +1500,230 @@ mod unit_tests {
         );
         assert_eq!(extract_fields(&rec, &[0..1, 3..4]), &["Captain"]);
         assert_eq!(extract_fields(&rec, &[1..2, 0..1]), &["Sham", "Captain"]);
+        assert_eq!(extract_fields(&rec, &[1..OPEN_END]), &["Sham", "12345"]);
+        assert_eq!(extract_fields(&rec, &[3..OPEN_END]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_complement() {
+        assert_eq!(complement(&[0..1], 3), vec![1..3]);
+        assert_eq!(complement(&[1..2], 3), vec![0..1, 2..3]);
+        assert_eq!(complement(&[2..3, 0..1], 3), vec![1..2]);
+        assert_eq!(complement(&[0..3], 3), PositionList::new());
+        assert_eq!(complement(&[0..2, 1..3], 5), vec![3..5]);
+        assert_eq!(complement(&[4..6], 3), vec![0..3]);
+        assert_eq!(complement(&[0..1], 0), PositionList::new());
+        assert_eq!(complement(&[2..OPEN_END], 5), vec![0..2]);
+    }
+
+    #[test]
+    fn test_merge_positions() {
+        assert_eq!(merge_positions(&[0..1, 0..1]), vec![0..1]);
+        assert_eq!(merge_positions(&[0..3, 1..2]), vec![0..3]);
+        assert_eq!(merge_positions(&[2..3, 0..1]), vec![0..1, 2..3]);
+        assert_eq!(merge_positions(&[1..2, 0..1]), vec![0..2]);
+        assert_eq!(merge_positions(&[4..OPEN_END, 0..2, 1..5]), vec![0..OPEN_END]);
+        assert_eq!(merge_positions(&[]), PositionList::new());
+
+        // Reorder mode keeps the given order including repetitions
+        let rec = StringRecord::from(vec!["Captain", "Sham", "12345"]);
+        assert_eq!(
+            extract_fields(&rec, &[0..3, 1..2]),
+            &["Captain", "Sham", "12345", "Sham"]
+        );
+        assert_eq!(
+            extract_fields(&rec, &merge_positions(&[0..3, 1..2])),
+            &["Captain", "Sham", "12345"]
+        );
+        assert_eq!(
+            extract_fields(&rec, &merge_positions(&[2..3, 0..1])),
+            &["Captain", "12345"]
+        );
+    }
+
+    #[test]
+    fn test_extract_complement() {
+        let rec = StringRecord::from(vec!["Captain", "Sham", "12345"]);
+        assert_eq!(
+            extract_fields(&rec, &complement(&[2..3], rec.len())),
+            &["Captain", "Sham"]
+        );
+        assert_eq!(
+            extract_fields(&rec, &complement(&[1..2, 0..1], rec.len())),
+            &["12345"]
+        );
+        assert_eq!(
+            extract_chars("ábc", &complement(&[0..1], 3)),
+            "bc".to_string()
+        );
+        assert_eq!(
+            extract_bytes("ábc", &complement(&[0..2], 4)),
+            "bc".to_string()
+        );
+    }
+
+    #[test]
+    fn test_is_delimited() {
+        assert!(is_delimited(&StringRecord::from(vec!["Captain", "Sham"])));
+        assert!(is_delimited(&StringRecord::from(vec!["", ""])));
+        assert!(!is_delimited(&StringRecord::from(vec!["Captain Sham"])));
+        assert!(!is_delimited(&StringRecord::new()));
+    }
+
+    #[test]
+    fn test_resolve_names() {
+        let header = StringRecord::from(vec!["id", "name", "e-mail", "phone"]);
+        let names = |names: &[&str]| -> Vec<String> {
+            names.iter().map(|name| { name.to_string() }).collect()
+        };
+
+        let res = resolve_names(&names(&["name"]), &header);
+        assert_eq!(res.unwrap(), vec![1..2]);
+
+        let res = resolve_names(&names(&["phone", "id"]), &header);
+        assert_eq!(res.unwrap(), vec![3..4, 0..1]);
+
+        // Names containing a dash take precedence over ranges
+        let res = resolve_names(&names(&["e-mail"]), &header);
+        assert_eq!(res.unwrap(), vec![2..3]);
+
+        let res = resolve_names(&names(&["id-e-mail", "e-mail-phone"]), &header);
+        assert_eq!(res.unwrap(), vec![0..3, 2..4]);
+
+        let res = resolve_names(&names(&["name-name"]), &header);
+        assert_eq!(res.unwrap(), vec![1..2]);
+
+        let res = resolve_names(&names(&["phone-id"]), &header);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Column \"phone\" must come before column \"id\""
+        );
+
+        let res = resolve_names(&names(&["id", "email"]), &header);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "unknown column \"email\", available columns: id, name, e-mail, phone"
+        );
+    }
+
+    #[test]
+    fn test_extract_raw_fields() {
+        let fields = split_raw_fields(b"Captain\tSham\t\"12345\"", b'\t');
+        assert_eq!(fields, vec![&b"Captain"[..], b"Sham", b"\"12345\""]);
+        assert_eq!(split_raw_fields(b"", b'\t'), vec![&b""[..]]);
+        assert_eq!(split_raw_fields(b"a,", b','), vec![&b"a"[..], b""]);
+
+        assert_eq!(extract_raw_fields(&fields, &[2..3]), vec![&b"\"12345\""[..]]);
+        assert_eq!(
+            extract_raw_fields(&fields, &[1..2, 0..1, 3..4]),
+            vec![&b"Sham"[..], b"Captain"]
+        );
+        assert_eq!(extract_raw_fields(&fields, &[1..OPEN_END]), vec![&b"Sham"[..], b"\"12345\""]);
+    }
+
+    #[test]
+    fn test_quote_field() {
+        assert_eq!(quote_field("Captain", ","), "Captain");
+        assert_eq!(quote_field("20,000", ","), "\"20,000\"");
+        assert_eq!(quote_field("20,000", "\t"), "20,000");
+        assert_eq!(quote_field("a -> b", " -> "), "\"a -> b\"");
+        assert_eq!(quote_field("say \"hi\"", "\t"), "\"say \"\"hi\"\"\"");
+        assert_eq!(quote_field("two\nlines", "\t"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_split_regex_fields() {
+        let re = BytesRegex::new("[ \t]+").unwrap();
+        assert_eq!(
+            split_regex_fields(b"742 pts/0 \t 00:00:00", &re),
+            vec![&b"742"[..], b"pts/0", b"00:00:00"]
+        );
+        assert_eq!(
+            split_regex_fields(trim_whitespace(b"  742  bash "), &re),
+            vec![&b"742"[..], b"bash"]
+        );
+        assert_eq!(split_regex_fields(trim_whitespace(b" \t "), &re), vec![&b""[..]]);
+
+        let re = BytesRegex::new(" *; *").unwrap();
+        assert_eq!(
+            split_regex_fields(b"a ; b;;c", &re),
+            vec![&b"a"[..], b"b", b"", b"c"]
+        );
+    }
+
+    #[test]
+    fn test_extract_graphemes() {
+        // "e" followed by a combining acute accent and a thumbs up with a
+        // skin tone modifier are single grapheme clusters
+        let line = "e\u{301}x\u{1F44D}\u{1F3FD}y";
+        assert_eq!(extract_chars(line, &[0..1]), "e".to_string());
+        assert_eq!(extract_graphemes(line, &[0..1]), "e\u{301}".to_string());
+        assert_eq!(extract_graphemes(line, &[2..3]), "\u{1F44D}\u{1F3FD}".to_string());
+        assert_eq!(extract_graphemes(line, &[3..4, 0..2]), "ye\u{301}x".to_string());
+        assert_eq!(extract_graphemes(line, &[1..OPEN_END]), "x\u{1F44D}\u{1F3FD}y".to_string());
+        assert_eq!(extract_graphemes(line, &[3..5]), "".to_string());
+        assert_eq!(extract_graphemes("", &[0..1]), "".to_string());
+    }
+
+    #[test]
+    fn test_extract_columns() {
+        // Each of the CJK characters occupies two columns
+        let line = "a日本b";
+        assert_eq!(extract_columns(line, &[0..1]), "a".to_string());
+        assert_eq!(extract_columns(line, &[0..3]), "a日".to_string());
+        assert_eq!(extract_columns(line, &[1..2]), "日".to_string());
+        assert_eq!(extract_columns(line, &[2..3]), "".to_string());
+        assert_eq!(extract_columns(line, &[3..OPEN_END]), "本b".to_string());
+        assert_eq!(extract_columns(line, &[5..6, 0..1]), "ba".to_string());
+        assert_eq!(extract_columns("e\u{301}x", &[0..1]), "e\u{301}".to_string());
+    }
+
+    #[test]
+    fn test_parse_widths() {
+        assert_eq!(parse_widths("5").unwrap(), vec![0..5]);
+        assert_eq!(parse_widths("5,10,3").unwrap(), vec![0..5, 5..15, 15..18]);
+        assert_eq!(
+            parse_widths("5,0").unwrap_err().to_string(),
+            "illegal width: \"0\""
+        );
+        assert_eq!(
+            parse_widths("5,+1").unwrap_err().to_string(),
+            "illegal width: \"+1\""
+        );
+        assert!(parse_widths("").is_err());
+        assert!(parse_widths("5,").is_err());
+        assert!(parse_widths("a").is_err());
+    }
+
+    #[test]
+    fn test_extract_fixed_width() {
+        let columns = parse_widths("5,10,3").unwrap();
+        assert_eq!(
+            extract_fixed_width("00042Émile Zola 1865", &columns, false),
+            vec!["00042", "Émile Zola", " 18"]
+        );
+        assert_eq!(
+            extract_fixed_width("00042Émile Zola 1865", &columns, true),
+            vec!["00042", "Émile Zola", "18"]
+        );
+        assert_eq!(
+            extract_fixed_width("00042Jules", &columns, false),
+            vec!["00042", "Jules", ""]
+        );
+        assert_eq!(extract_fixed_width("", &columns, false), vec!["", "", ""]);
+        assert_eq!(
+            extract_fixed_width("00042Jules", &[5..OPEN_END, 0..2], false),
+            vec!["Jules", "00"]
+        );
+    }
+
+    #[test]
+    fn test_escape_tsv_field() {
+        assert_eq!(escape_tsv_field("Émile Zola"), "Émile Zola");
+        assert_eq!(escape_tsv_field("a\tb"), "a\\tb");
+        assert_eq!(escape_tsv_field("a\r\nb"), "a\\r\\nb");
+        assert_eq!(escape_tsv_field("C:\\tmp"), "C:\\\\tmp");
+        assert_eq!(escape_tsv_field(""), "");
     }
 
 }
@@ -9,6 +9,14 @@ const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const UNDELIMITED: &str = "tests/inputs/movies3.tsv";
+const RAGGED: &str = "tests/inputs/ragged.tsv";
+const BOOKS_CSV: &str = "tests/inputs/books.csv";
+const PS: &str = "tests/inputs/ps.txt";
+const SEMICOLONS: &str = "tests/inputs/semicolons.txt";
+const GLYPHS: &str = "tests/inputs/glyphs.txt";
+const FIXED: &str = "tests/inputs/books.fixed";
+const INVALID_UTF8: &str = "tests/inputs/invalid_utf8.csv";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -35,10 +43,12 @@ fn skips_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&["-f", "1", CSV, &bad, TSV])
+        .args(["-f", "1", CSV, &bad, TSV])
         .assert()
-        .success()
-        .stderr(predicate::str::is_match(expected)?);
+        .failure()
+        .stdout(predicate::str::contains("The Blues Brothers"))
+        .stderr(predicate::str::is_match(expected)?)
+        .stderr(predicate::str::contains("1 of 3 inputs could not be read"));
     Ok(())
 }
 
@@ -110,7 +120,7 @@ fn dies_bad_delimiter() -> TestResult {
 #[test]
 fn dies_chars_bytes_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-f", "1", "-b", "1"])
+        .args([CSV, "-c", "1", "-f", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -120,7 +130,7 @@ fn dies_chars_bytes_fields() -> TestResult {
 #[test]
 fn dies_bytes_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-f", "1", "-b", "1"])
+        .args([CSV, "-f", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -130,7 +140,7 @@ fn dies_bytes_fields() -> TestResult {
 #[test]
 fn dies_chars_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-f", "1"])
+        .args([CSV, "-c", "1", "-f", "1"])
         .assert()
         .failure();
     Ok(())
@@ -140,7 +150,7 @@ fn dies_chars_fields() -> TestResult {
 #[test]
 fn dies_chars_bytes() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-b", "1"])
+        .args([CSV, "-c", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -337,3 +347,542 @@ fn tsv_c1_8() -> TestResult {
 fn repeated_value() -> TestResult {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_complement() -> TestResult {
+    run(
+        &[TSV, "-f", "2", "--complement"],
+        "tests/expected/movies1.tsv.f2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f1_3_complement() -> TestResult {
+    run(
+        &[CSV, "-f", "1,3", "-d", ",", "--complement"],
+        "tests/expected/movies1.csv.f1,3.dcomma.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c2_3_complement() -> TestResult {
+    run(
+        &[TSV, "-c", "2-3", "--complement"],
+        "tests/expected/movies1.tsv.c2-3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b1_complement() -> TestResult {
+    run(
+        &[TSV, "-b", "1", "--complement"],
+        "tests/expected/movies1.tsv.b1.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open() -> TestResult {
+    run(&[TSV, "-f", "2-"], "tests/expected/movies1.tsv.f2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_open_3() -> TestResult {
+    run(&[TSV, "-c", "-3"], "tests/expected/movies1.tsv.c-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b5_open() -> TestResult {
+    run(&[TSV, "-b", "5-"], "tests/expected/movies1.tsv.b5-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f_all() -> TestResult {
+    run(&[TSV, "-f", "-"], "tests/expected/movies1.tsv.f1-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_undelimited() -> TestResult {
+    run(&[UNDELIMITED, "-f", "2"], "tests/expected/movies3.tsv.f2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_only_delimited() -> TestResult {
+    run(
+        &[UNDELIMITED, "-f", "2", "-s"],
+        "tests/expected/movies3.tsv.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", " → "],
+        "tests/expected/movies1.tsv.f1,3.arrow.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f1_2_output_delimiter() -> TestResult {
+    run(
+        &[CSV, "-f", "1-2", "-d", ",", "--output-delimiter", "\t"],
+        "tests/expected/movies1.csv.f1-2.dcomma.tab.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_field_names() -> TestResult {
+    run(
+        &[TSV, "-F", "title,director"],
+        "tests/expected/movies1.tsv.Ftitle,director.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_field_name_range() -> TestResult {
+    run(
+        &[CSV, "-F", "title-year", "-d", ","],
+        "tests/expected/movies1.csv.f1-2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn warns_unknown_field_name() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([TSV, "-F", "title,rating"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "unknown column \"rating\", available columns: title, year, director",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_no_header() -> TestResult {
+    run(&[TSV, "-f", "2", "--no-header"], "tests/expected/movies1.tsv.f2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_field_names_no_header() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([TSV, "-F", "title", "--no-header"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ragged_f2_3() -> TestResult {
+    run(&[RAGGED, "-f", "2,3"], "tests/expected/ragged.tsv.f2-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ragged_f3_open_only_delimited() -> TestResult {
+    run(
+        &[RAGGED, "-f", "3-", "-s"],
+        "tests/expected/ragged.tsv.f3-.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ragged_f2_3_csv() -> TestResult {
+    run(
+        &[RAGGED, "-f", "2,3", "--csv"],
+        "tests/expected/ragged.tsv.f2-3.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn books_csv_f3_literal() -> TestResult {
+    run(
+        &[BOOKS_CSV, "-f", "3", "-d", ","],
+        "tests/expected/books.csv.f3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn books_csv_f3_csv() -> TestResult {
+    run(
+        &[BOOKS_CSV, "-f", "3", "-d", ",", "--csv"],
+        "tests/expected/books.csv.f3.dcomma.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_f1_4() -> TestResult {
+    run(&[PS, "-w", "-f", "1,4"], "tests/expected/ps.txt.w.f1,4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn delim_regex_f2_1() -> TestResult {
+    run(
+        &[
+            SEMICOLONS,
+            "--delim-regex",
+            " *; *",
+            "-f",
+            "2,1",
+            "--output-delimiter",
+            ",",
+        ],
+        "tests/expected/semicolons.txt.regex.f2,1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_delim_regex() -> TestResult {
+    dies(
+        &[PS, "-f", "1", "--delim-regex", "("],
+        "--delim-regex \"(\" is not a valid regex",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_whitespace_and_delimiter() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([PS, "-f", "1", "-w", "-d", ","])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn glyphs_c1_graphemes() -> TestResult {
+    run(
+        &[GLYPHS, "-c", "1", "--graphemes"],
+        "tests/expected/glyphs.txt.c1.graphemes.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn glyphs_c1_4_width() -> TestResult {
+    run(
+        &[GLYPHS, "-c", "1-4", "--width"],
+        "tests/expected/glyphs.txt.c1-4.width.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_graphemes_without_chars() -> TestResult {
+    dies(
+        &[GLYPHS, "-b", "1", "--graphemes"],
+        "--graphemes and --width require --chars",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b1_8_no_split() -> TestResult {
+    run(&[TSV, "-b", "1-8", "-n"], "tests/expected/movies1.tsv.b1-8.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn raw_bytes() -> TestResult {
+    let input: &[u8] = b"\xffab\xfe\xc3\xa1\ncd\r\n\x00\x01";
+    Command::cargo_bin(PRG)?
+        .args(["-b", "1,4-5", "--raw"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(&b"\xff\xfe\xc3\nc\n\x00\n"[..]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn raw_bytes_no_split() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([TSV, "-b", "1-8", "-n", "--raw"])
+        .assert()
+        .success()
+        .stdout(fs::read("tests/expected/movies1.tsv.b1-8.n.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_raw_without_bytes() -> TestResult {
+    dies(&[TSV, "-c", "1", "--raw"], "-n and --raw require --bytes")
+}
+
+// --------------------------------------------------
+#[test]
+fn repeated_value_posix_order() -> TestResult {
+    run(
+        &[BOOKS, "-c", "1,1", "--posix-order"],
+        "tests/expected/books.c1,1.posix.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_overlapping_reorder() -> TestResult {
+    run(
+        &[TSV, "-f", "3,1-2,2", "--reorder"],
+        "tests/expected/movies1.tsv.f3,1-2,2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_overlapping_posix_order() -> TestResult {
+    run(
+        &[TSV, "-f", "3,1-2,2", "--posix-order"],
+        "tests/expected/movies1.tsv.f3,1-2,2.posix.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn reorder_overrides_posix_order() -> TestResult {
+    run(
+        &[TSV, "-f", "3,1-2,2", "--posix-order", "--reorder"],
+        "tests/expected/movies1.tsv.f3,1-2,2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_widths() -> TestResult {
+    run(
+        &[FIXED, "--widths", "5,12,4"],
+        "tests/expected/books.fixed.w5,12,4.out",
+    )?;
+    run(
+        &[FIXED, "--field-widths", "5,12,4"],
+        "tests/expected/books.fixed.w5,12,4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_widths_trim() -> TestResult {
+    run(
+        &[FIXED, "--field-widths", "5,12,4", "--trim"],
+        "tests/expected/books.fixed.w5,12,4.trim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_columns_trim_csv() -> TestResult {
+    run(
+        &[
+            FIXED,
+            "--columns",
+            "6-17,1-5",
+            "--trim",
+            "--csv",
+            "--output-delimiter",
+            ",",
+        ],
+        "tests/expected/books.fixed.c6-17,1-5.trim.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_columns_complement() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([FIXED, "--columns", "6-17", "--complement", "--trim"])
+        .assert()
+        .success()
+        .stdout("ID\tYEAR\n00001\t1865\n00002\t1870\n00003\t1952\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_widths_output_format() -> TestResult {
+    run(
+        &[FIXED, "--field-widths", "5,12,4", "--trim", "--output-format", "json"],
+        "tests/expected/books.fixed.w5,12,4.trim.json.out",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args([FIXED, "--field-widths", "5,12", "--trim", "--output-format", "csv"])
+        .assert()
+        .success()
+        .stdout("ID,AUTHOR\n00001,Émile Zola\n00002,Jules Verne\n00003,\"S. Beckett,\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_width() -> TestResult {
+    dies(&[FIXED, "--field-widths", "5,0"], "illegal width: \"0\"")
+}
+
+// --------------------------------------------------
+#[test]
+fn output_format_tsv() -> TestResult {
+    run(
+        &[TSV, "-f", "1,2", "--output-format", "tsv"],
+        "tests/expected/movies1.tsv.f1,2.tsv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_format_csv() -> TestResult {
+    run(
+        &[TSV, "-f", "1,2", "--output-format", "csv"],
+        "tests/expected/movies1.tsv.f1,2.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_format_json() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-format", "json"],
+        "tests/expected/movies1.tsv.f1,3.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_format_jsonl() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-format", "jsonl"],
+        "tests/expected/movies1.tsv.f1,3.jsonl.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_format_json_csv_input() -> TestResult {
+    run(
+        &[BOOKS_CSV, "-d", ",", "--csv", "-f", "3,1", "--output-format", "json"],
+        "tests/expected/books.csv.f3,1.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_format_jsonl_no_header() -> TestResult {
+    run(
+        &[
+            BOOKS_CSV,
+            "-d",
+            ",",
+            "--csv",
+            "-f",
+            "3",
+            "--no-header",
+            "--output-format",
+            "jsonl",
+        ],
+        "tests/expected/books.csv.f3.noheader.jsonl.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_format_json_multiple_files() -> TestResult {
+    run(
+        &[TSV, BOOKS, "-f", "1,3", "--output-format", "json"],
+        "tests/expected/movies1.tsv+books.tsv.f1,3.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn output_format_undelimited_line() -> TestResult {
+    let input = "a\tb\tc\nx\n";
+    for (format, expected) in [
+        ("tsv", "b\nx\n"),
+        ("csv", "b\nx\n"),
+        ("json", "[]\n"),
+        ("jsonl", ""),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(["-f", "2", "--output-format", format])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_output_format() -> TestResult {
+    dies(&[TSV, "-f", "1", "--output-format", "xml"], "[possible values: tsv, csv, json, jsonl]")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_output_format_without_fields() -> TestResult {
+    for mode in [["-c", "1"], ["-b", "1"]] {
+        dies(
+            &[TSV, mode[0], mode[1], "--output-format", "json"],
+            "--output-format requires --fields, -F or fixed-width fields",
+        )?;
+    }
+    dies(
+        &[TSV, "-b", "1", "--raw", "--output-format", "tsv"],
+        "--output-format requires --fields, -F or fixed-width fields",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn reports_read_error_line() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1-3", INVALID_UTF8, TSV])
+        .assert()
+        .failure()
+        .stdout("id,\ntit\nThe\nLes\n")
+        .stderr(predicate::str::contains(format!(
+            "{}: line 2: stream did not contain valid UTF-8",
+            INVALID_UTF8
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn reports_csv_parse_error() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "-d", ",", "-f", "1", INVALID_UTF8])
+        .assert()
+        .failure()
+        .stdout("id\n")
+        .stderr(predicate::str::is_match(format!(
+            "{}: .*line 2.*invalid utf-8",
+            INVALID_UTF8
+        ))?);
+    Ok(())
+}
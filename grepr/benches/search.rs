@@ -0,0 +1,76 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use grepr::{MyResult, RegexMatcher, Searcher};
+use memmap2::Mmap;
+use regex::Regex;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+const NUM_LINES: usize = 500_000;
+
+/// With `near_misses`, every other line has the level "error", which only
+/// differs in case from the "ERROR" that is searched for
+fn create_log_file(name: &str, near_misses: bool) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    let mut writer = BufWriter::new(File::create(&path).unwrap());
+    for i in 0..NUM_LINES {
+        let level = if i % 1000 == 0 {
+            "ERROR"
+        } else if near_misses && i % 2 == 0 {
+            "error"
+        } else {
+            "INFO"
+        };
+        writeln!(
+            writer,
+            "2023-01-01T00:00:00 {} request {} served in {} ms",
+            level,
+            i,
+            i % 97
+        )
+        .unwrap();
+    }
+    path
+}
+
+fn bench_search(c: &mut Criterion) {
+    let path = create_log_file("grepr-bench.log", false);
+    let near_misses_path = create_log_file("grepr-bench-near-misses.log", true);
+    let searcher = Searcher::new(RegexMatcher::new(Regex::new("ERROR").unwrap(), false));
+    let mut sink = |_, _: &str| -> MyResult<bool> { Ok(true) };
+
+    let mut group = c.benchmark_group("search");
+    group.sample_size(20);
+
+    group.bench_function("read", |b| {
+        b.iter(|| {
+            let file = File::open(&path).unwrap();
+            searcher.search(file, &mut sink).unwrap()
+        })
+    });
+
+    group.bench_function("mmap", |b| {
+        b.iter(|| {
+            let file = File::open(&path).unwrap();
+            let mmap = unsafe { Mmap::map(&file).unwrap() };
+            searcher.search_slice(&mmap, &mut sink).unwrap()
+        })
+    });
+
+    group.bench_function("mmap_case_near_misses", |b| {
+        b.iter(|| {
+            let file = File::open(&near_misses_path).unwrap();
+            let mmap = unsafe { Mmap::map(&file).unwrap() };
+            searcher.search_slice(&mmap, &mut sink).unwrap()
+        })
+    });
+
+    group.finish();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&near_misses_path).unwrap();
+}
+
+criterion_group!(benches, bench_search);
+criterion_main!(benches);
@@ -1,6 +1,8 @@
-use std::{error::Error, fmt::Debug, path::Path, io::{BufRead, BufReader, self}, fs::File};
+use std::{error::Error, fmt::Debug, path::Path, io::{BufRead, BufReader, Read, self}, fs::File,
+    time::{Duration, Instant}};
 
-use clap::{Parser, command, crate_authors, crate_version, ArgAction};
+use clap::{Parser, crate_authors, crate_version, ArgAction};
+use memmap2::Mmap;
 use regex::{RegexBuilder, Regex};
 use walkdir::WalkDir;
 
@@ -78,15 +80,81 @@ struct Arguments {
         help = "Invert match"
     )]
     invert_match: bool,
+
+    #[arg(
+        long = "stats",
+        action = ArgAction::SetTrue,
+        help = "Print search statistics to stderr"
+    )]
+    stats: bool,
+
+    #[arg(
+        long = "mmap",
+        action = ArgAction::SetTrue,
+        overrides_with = "no_mmap",
+        help = "Always search regular files via memory maps"
+    )]
+    mmap: bool,
+
+    #[arg(
+        long = "no-mmap",
+        action = ArgAction::SetTrue,
+        overrides_with = "mmap",
+        help = "Never search files via memory maps"
+    )]
+    no_mmap: bool,
+}
+
+/// Regular files of at least this size are memory-mapped by default
+pub const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MmapChoice {
+    /// Memory-map regular files of at least `MMAP_THRESHOLD` bytes
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Debug)]
 pub struct Config {
     pub pattern: Regex,
+    pub insensitive: bool,
     pub files: Vec<String>,
     pub recursive: bool,
     pub count: bool,
     pub invert_match: bool,
+    pub stats: bool,
+    pub mmap: MmapChoice,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchStats {
+    pub files_searched: usize,
+    pub files_with_matches: usize,
+    pub matched_lines: usize,
+    pub bytes_scanned: usize,
+    pub elapsed: Duration,
+}
+
+impl std::ops::AddAssign for SearchStats {
+    fn add_assign(&mut self, other: SearchStats) {
+        self.files_searched += other.files_searched;
+        self.files_with_matches += other.files_with_matches;
+        self.matched_lines += other.matched_lines;
+        self.bytes_scanned += other.bytes_scanned;
+        self.elapsed += other.elapsed;
+    }
+}
+
+impl std::fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Files searched:     {}", self.files_searched)?;
+        writeln!(f, "Files with matches: {}", self.files_with_matches)?;
+        writeln!(f, "Matched lines:      {}", self.matched_lines)?;
+        writeln!(f, "Bytes scanned:      {}", self.bytes_scanned)?;
+        write!(f, "Elapsed time:       {:.3}s", self.elapsed.as_secs_f64())
+    }
 }
 
 pub fn get_config() -> MyResult<Config> {
@@ -103,33 +171,62 @@ pub fn get_config() -> MyResult<Config> {
 
     Ok(Config {
         pattern: regex,
+        insensitive: args.insensitive,
         files: args.files,
         recursive: args.recursive,
         count: args.count,
         invert_match: args.invert_match,
+        stats: args.stats,
+        mmap: if args.mmap {
+            MmapChoice::Always
+        } else if args.no_mmap {
+            MmapChoice::Never
+        } else {
+            MmapChoice::Auto
+        },
     })
 
 }
 
-pub fn run(config: &Config) -> MyResult<()> {
+pub fn run(config: &Config) -> MyResult<SearchStats> {
 
+    let start = Instant::now();
+    let mut stats = SearchStats::default();
     let files = find_files(&config.files, config.recursive);
     let many_files = files.len() > 1;
 
+    let matcher: Box<dyn Matcher> = if config.invert_match {
+        Box::new(InvertedMatcher::new(RegexMatcher::new(config.pattern.clone(), config.insensitive)))
+    } else {
+        Box::new(RegexMatcher::new(config.pattern.clone(), config.insensitive))
+    };
+    let searcher = Searcher::new(matcher);
+
     for result in files {
 
         match result {
             Ok(file_path) => {
-                match open(&file_path) {
-                    Ok(mut file) => {
-                        let lines =
-                            find_lines(&mut file, &config.pattern, config.invert_match)?;
+                match open(&file_path, config.mmap) {
+                    Ok(input) => {
                         let file_path_opt = if many_files {
                             Some(file_path.as_str())
                         } else {
                             None
                         };
-                        print_result(&lines, file_path_opt, &config);
+                        let mut sink = PrintSink {
+                            file_path: file_path_opt,
+                            count: config.count,
+                        };
+                        let file_stats = match input {
+                            Input::Reader(reader) =>
+                                searcher.search(reader, &mut sink)?,
+                            Input::Mapped(mmap) =>
+                                searcher.search_slice(&mmap, &mut sink)?,
+                        };
+                        if config.count {
+                            sink.print_count(file_stats.matched_lines);
+                        }
+                        stats += file_stats;
                     },
                     Err(e) => eprintln!("{}: {}", file_path, e),
                 };
@@ -139,7 +236,12 @@ pub fn run(config: &Config) -> MyResult<()> {
 
     }
 
-    Ok(())
+    stats.elapsed = start.elapsed();
+    if config.stats {
+        eprintln!("{}", stats);
+    }
+
+    Ok(stats)
 }
 
 fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
@@ -197,65 +299,370 @@ fn find_files_in_path(file_path: &str, recursive: bool) -> Vec<MyResult<String>>
     results
 }
 
-fn open(file_path: &str) -> MyResult<Box<dyn BufRead>> {
-    match file_path {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(file_path)?))),
+enum Input {
+    Reader(Box<dyn Read>),
+    Mapped(Mmap),
+}
+
+fn open(file_path: &str, mmap: MmapChoice) -> MyResult<Input> {
+    if file_path == "-" {
+        return Ok(Input::Reader(Box::new(io::stdin())));
+    }
+
+    let file = File::open(file_path)?;
+    let metadata = file.metadata()?;
+    let use_mmap = metadata.is_file() && match mmap {
+        MmapChoice::Auto => metadata.len() >= MMAP_THRESHOLD,
+        MmapChoice::Always => true,
+        MmapChoice::Never => false,
+    };
+
+    if use_mmap {
+        // SAFETY: the map is only read while searching. Should another
+        // process truncate the file meanwhile, the behaviour is the same as
+        // for any other mmap based tool (e.g. a SIGBUS on Linux).
+        if let Ok(mmap) = unsafe { Mmap::map(&file) } {
+            return Ok(Input::Mapped(mmap));
+        }
     }
+
+    Ok(Input::Reader(Box::new(file)))
 }
 
-fn find_lines(
-    file: &mut impl BufRead,
-    pattern: &Regex,
-    invert_match: bool) -> MyResult<Vec<String>>
-{
-    let mut ret = vec![];
-    let mut buf = String::new();
-
-    while let Ok(num_bytes) = file.read_line(&mut buf) {
-        if num_bytes > 0 {
-            let line = buf.to_owned();
-            let matched = pattern.is_match(&line);
-            if matched != invert_match {
-                ret.push(line);
-            }
-            buf.clear();
+/// Decides whether a single line (without its line terminator) is a match.
+pub trait Matcher {
+    fn is_match(&self, line: &str) -> bool;
+
+    /// Returns an offset at or after `start` within the first line of
+    /// `haystack` that may contain a match. Lines are still verified with
+    /// `is_match`, so false positives are allowed but no matching line may
+    /// be skipped. By default every line is a candidate.
+    fn find_candidate(&self, haystack: &str, start: usize) -> Option<usize> {
+        if start < haystack.len() {
+            Some(start)
         } else {
-            break;
+            None
         }
     }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn is_match(&self, line: &str) -> bool {
+        (**self).is_match(line)
+    }
 
-    Ok(ret)
+    fn find_candidate(&self, haystack: &str, start: usize) -> Option<usize> {
+        (**self).find_candidate(haystack, start)
+    }
 }
 
-fn print_result(lines: &Vec<String>, file_path: Option<&str>, config: &Config) {
-    let many_files = file_path.is_some();
+/// Matches lines against a regular expression.
+#[derive(Debug, Clone)]
+pub struct RegexMatcher {
+    regex: Regex,
+    buffer_regex: Option<Regex>,
+}
 
-    if !config.count {
-        for line in lines {
-            if !many_files {
-                print!("{}", line);
-            } else {
-                print!("{}:{}", file_path.unwrap(), line);
-            }
+impl RegexMatcher {
+    /// `case_insensitive` must be the case flag `regex` was built with, as
+    /// the regex for searching whole buffers is built from its pattern.
+    pub fn new(regex: Regex, case_insensitive: bool) -> RegexMatcher {
+        let buffer_regex = Self::build_buffer_regex(&regex, case_insensitive);
+        RegexMatcher { regex, buffer_regex }
+    }
+
+    // The buffer regex runs over many lines at once, so anchors must match
+    // at line boundaries. It only yields candidates, which are checked line
+    // by line. Patterns that anchor at the text boundaries, or turn off
+    // multi-line mode with an inline flag, are left to the line by line search.
+    fn build_buffer_regex(regex: &Regex, case_insensitive: bool) -> Option<Regex> {
+        let pattern = regex.as_str();
+        let disables_multi_line = Regex::new(r"\(\?[a-zA-Z]*-[a-zA-Z]*m[a-zA-Z]*[:)]")
+            .map(|flags| flags.is_match(pattern))
+            .unwrap_or(true);
+        if pattern.contains("\\A") || pattern.contains("\\z") || disables_multi_line {
+            return None;
         }
-    } else {
-        if !many_files {
-            println!("{}", lines.len());
+        RegexBuilder::new(pattern)
+            .multi_line(true)
+            .crlf(true)
+            .case_insensitive(case_insensitive)
+            .build()
+            .ok()
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn is_match(&self, line: &str) -> bool {
+        self.regex.is_match(line)
+    }
+
+    fn find_candidate(&self, haystack: &str, start: usize) -> Option<usize> {
+        match &self.buffer_regex {
+            Some(regex) => regex.find_at(haystack, start).map(|m| m.start()),
+            None if start < haystack.len() => Some(start),
+            None => None,
+        }
+    }
+}
+
+/// Matches lines containing a fixed string.
+#[derive(Debug, Clone)]
+pub struct LiteralMatcher {
+    literal: String,
+    case_insensitive: bool,
+}
+
+impl LiteralMatcher {
+    pub fn new(literal: &str, case_insensitive: bool) -> LiteralMatcher {
+        let literal = if case_insensitive {
+            literal.to_lowercase()
+        } else {
+            literal.to_owned()
+        };
+        LiteralMatcher { literal, case_insensitive }
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn is_match(&self, line: &str) -> bool {
+        if self.case_insensitive {
+            line.to_lowercase().contains(&self.literal)
         } else {
-            println!("{}:{}", file_path.unwrap(), lines.len());
+            line.contains(&self.literal)
+        }
+    }
+
+    fn find_candidate(&self, haystack: &str, start: usize) -> Option<usize> {
+        if self.case_insensitive {
+            if start < haystack.len() { Some(start) } else { None }
+        } else {
+            haystack[start..].find(&self.literal).map(|pos| start + pos)
         }
     }
 }
 
+/// Matches exactly the lines the wrapped matcher rejects.
+#[derive(Debug, Clone)]
+pub struct InvertedMatcher<M: Matcher> {
+    inner: M,
+}
+
+impl<M: Matcher> InvertedMatcher<M> {
+    pub fn new(inner: M) -> InvertedMatcher<M> {
+        InvertedMatcher { inner }
+    }
+}
+
+impl<M: Matcher> Matcher for InvertedMatcher<M> {
+    fn is_match(&self, line: &str) -> bool {
+        !self.inner.is_match(line)
+    }
+}
+
+/// Receives the matching lines of a search.
+///
+/// `line_number` is 1-based and `line` includes its line terminator.
+/// Returning `Ok(false)` stops the search early.
+pub trait Sink {
+    fn matched(&mut self, line_number: usize, line: &str) -> MyResult<bool>;
+}
+
+impl<F> Sink for F
+where
+    F: FnMut(usize, &str) -> MyResult<bool>,
+{
+    fn matched(&mut self, line_number: usize, line: &str) -> MyResult<bool> {
+        self(line_number, line)
+    }
+}
+
+/// Searches line-oriented input with a `Matcher`.
+#[derive(Debug, Clone)]
+pub struct Searcher<M: Matcher> {
+    matcher: M,
+}
+
+impl<M: Matcher> Searcher<M> {
+    pub fn new(matcher: M) -> Searcher<M> {
+        Searcher { matcher }
+    }
+
+    pub fn matcher(&self) -> &M {
+        &self.matcher
+    }
+
+    /// Reports every matching line of `reader` to `sink`. The returned
+    /// statistics cover a single input.
+    pub fn search<R: Read, S: Sink + ?Sized>(
+        &self,
+        reader: R,
+        sink: &mut S) -> MyResult<SearchStats>
+    {
+        let start = Instant::now();
+        let mut stats = SearchStats {
+            files_searched: 1,
+            ..SearchStats::default()
+        };
+        let mut reader = BufReader::new(reader);
+        let mut buf = vec![];
+        let mut line_number = 0;
+
+        loop {
+            buf.clear();
+            let num_bytes = reader.read_until(b'\n', &mut buf)?;
+            if num_bytes == 0 {
+                break;
+            }
+            stats.bytes_scanned += num_bytes;
+            line_number += 1;
+
+            let line = String::from_utf8_lossy(&buf);
+            if self.matcher.is_match(strip_line_terminator(&line)) {
+                stats.matched_lines += 1;
+                if !sink.matched(line_number, &line)? {
+                    break;
+                }
+            }
+        }
+
+        if stats.matched_lines > 0 {
+            stats.files_with_matches = 1;
+        }
+        stats.elapsed = start.elapsed();
+
+        Ok(stats)
+    }
+
+    /// Reports every matching line of an in-memory buffer to `sink`. The
+    /// matcher is run over the whole buffer and only the lines around
+    /// candidate matches are materialized. Buffers which are not valid
+    /// UTF-8 are searched line by line like any other input.
+    pub fn search_slice<S: Sink + ?Sized>(
+        &self,
+        haystack: &[u8],
+        sink: &mut S) -> MyResult<SearchStats>
+    {
+        let text = match std::str::from_utf8(haystack) {
+            Ok(text) => text,
+            Err(_) => return self.search(haystack, sink),
+        };
+
+        let start = Instant::now();
+        let mut stats = SearchStats {
+            files_searched: 1,
+            bytes_scanned: text.len(),
+            ..SearchStats::default()
+        };
+        let ends_with_newline = text.ends_with('\n');
+        let mut pos = 0;
+        let mut counted_to = 0;
+        let mut newlines = 0;
+
+        while let Some(candidate) = self.matcher.find_candidate(text, pos) {
+            // A candidate at the very end belongs to no line if the text
+            // ends with a line terminator
+            if candidate >= text.len() && (text.is_empty() || ends_with_newline) {
+                break;
+            }
+
+            let line_start = text[pos..candidate]
+                .rfind('\n')
+                .map_or(pos, |offset| pos + offset + 1);
+            let line_end = text[candidate..]
+                .find('\n')
+                .map_or(text.len(), |offset| candidate + offset + 1);
+            let line = &text[line_start..line_end];
+
+            if self.matcher.is_match(strip_line_terminator(line)) {
+                newlines += text[counted_to..line_start]
+                    .bytes()
+                    .filter(|&byte| byte == b'\n')
+                    .count();
+                counted_to = line_start;
+                stats.matched_lines += 1;
+                if !sink.matched(newlines + 1, line)? {
+                    stats.bytes_scanned = line_end;
+                    break;
+                }
+            }
+
+            pos = line_end;
+            if pos >= text.len() {
+                break;
+            }
+        }
+
+        if stats.matched_lines > 0 {
+            stats.files_with_matches = 1;
+        }
+        stats.elapsed = start.elapsed();
+
+        Ok(stats)
+    }
+}
+
+fn strip_line_terminator(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+struct PrintSink<'a> {
+    file_path: Option<&'a str>,
+    count: bool,
+}
+
+impl PrintSink<'_> {
+    fn print_count(&self, count: usize) {
+        match self.file_path {
+            Some(file_path) => println!("{}:{}", file_path, count),
+            None => println!("{}", count),
+        }
+    }
+}
+
+impl Sink for PrintSink<'_> {
+    fn matched(&mut self, _line_number: usize, line: &str) -> MyResult<bool> {
+        if !self.count {
+            match self.file_path {
+                Some(file_path) => print!("{}:{}", file_path, line),
+                None => print!("{}", line),
+            }
+        }
+        Ok(true)
+    }
+}
+
 // --------------------------------------------------
 #[cfg(test)]
 mod tests {
 
-    use super::{find_files, find_lines};
+    use super::{
+        find_files, InvertedMatcher, LiteralMatcher, Matcher, MyResult,
+        RegexMatcher, Searcher,
+    };
     use rand::{distributions::Alphanumeric, Rng};
     use regex::{Regex, RegexBuilder};
-    use std::io::Cursor;
+    use std::io::{Cursor, Read};
+
+    fn find_lines(
+        file: &mut impl Read,
+        pattern: &Regex,
+        insensitive: bool,
+        invert_match: bool) -> MyResult<Vec<String>>
+    {
+        let matcher: Box<dyn Matcher> = if invert_match {
+            Box::new(InvertedMatcher::new(RegexMatcher::new(pattern.clone(), insensitive)))
+        } else {
+            Box::new(RegexMatcher::new(pattern.clone(), insensitive))
+        };
+        let mut lines = vec![];
+        Searcher::new(matcher).search(file, &mut |_, line: &str| {
+            lines.push(line.to_string());
+            Ok(true)
+        })?;
+        Ok(lines)
+    }
 
     #[test]
     fn test_find_files() {
@@ -310,12 +717,12 @@ mod tests {
 
         // The pattern _or_ should match the one line, "Lorem"
         let re1 = Regex::new("or").unwrap();
-        let matches = find_lines(&mut Cursor::new(&text), &re1, false);
+        let matches = find_lines(&mut Cursor::new(&text), &re1, false, false);
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 1);
 
         // When inverted, the function should match the other two lines
-        let matches = find_lines(&mut Cursor::new(&text), &re1, true);
+        let matches = find_lines(&mut Cursor::new(&text), &re1, false, true);
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 2);
 
@@ -326,14 +733,137 @@ mod tests {
             .unwrap();
 
         // The two lines "Lorem" and "DOLOR" should match
-        let matches = find_lines(&mut Cursor::new(&text), &re2, false);
+        let matches = find_lines(&mut Cursor::new(&text), &re2, true, false);
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 2);
 
         // When inverted, the one remaining line should match
-        let matches = find_lines(&mut Cursor::new(&text), &re2, true);
+        let matches = find_lines(&mut Cursor::new(&text), &re2, true, true);
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 1);
     }
 
+    #[test]
+    fn test_searcher() {
+        let text = b"Lorem\nIpsum\r\nDOLOR";
+        let searcher = Searcher::new(LiteralMatcher::new("OR", true));
+
+        // Line numbers are reported along with the matching lines
+        let mut matches = vec![];
+        let stats = searcher
+            .search(Cursor::new(&text), &mut |line_number, line: &str| {
+                matches.push((line_number, line.to_string()));
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(
+            matches,
+            vec![(1, "Lorem\n".to_string()), (3, "DOLOR".to_string())]
+        );
+        assert_eq!(stats.files_searched, 1);
+        assert_eq!(stats.files_with_matches, 1);
+        assert_eq!(stats.matched_lines, 2);
+        assert_eq!(stats.bytes_scanned, text.len());
+
+        // The sink can stop the search after the first match
+        let mut count = 0;
+        let stats = searcher
+            .search(Cursor::new(&text), &mut |_, _: &str| {
+                count += 1;
+                Ok(false)
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(stats.matched_lines, 1);
+
+        // A case-sensitive literal only matches the line with the same case
+        let searcher = Searcher::new(LiteralMatcher::new("OR", false));
+        let stats = searcher
+            .search(Cursor::new(&text), &mut |_, _: &str| Ok(true))
+            .unwrap();
+        assert_eq!(stats.matched_lines, 1);
+        assert_eq!(stats.files_with_matches, 1);
+    }
+
+    #[test]
+    fn test_search_slice() {
+        let text = "Lorem\nIpsum\r\ndolor sit\n\nAMET\r\nlorem";
+
+        let collect = |matcher: Box<dyn Matcher>, slice: bool| {
+            let searcher = Searcher::new(matcher);
+            let mut lines = vec![];
+            let mut sink = |line_number, line: &str| -> MyResult<bool> {
+                lines.push((line_number, line.to_string()));
+                Ok(true)
+            };
+            let stats = if slice {
+                searcher.search_slice(text.as_bytes(), &mut sink).unwrap()
+            } else {
+                searcher.search(text.as_bytes(), &mut sink).unwrap()
+            };
+            (lines, stats.matched_lines, stats.bytes_scanned)
+        };
+
+        // The buffer search must report the same lines as the line by line
+        // search, including anchored and inverted patterns
+        for (pattern, insensitive) in [
+            ("or", false),
+            ("or", true),
+            ("amet", false),
+            ("AMET", false),
+            ("^L", false),
+            ("m$", false),
+            ("^$", false),
+            ("", false),
+            ("ipsum$", true),
+            ("\\Alorem", true),
+            ("(?-m)^Ipsum", false),
+            ("(?-m:m$)", false),
+            ("(?s-m)^dolor", false),
+            ("(?i-m)^amet$", false),
+            ("\\s", false),
+            ("xyz", false),
+        ] {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(insensitive)
+                .build()
+                .unwrap();
+            let matcher = || Box::new(RegexMatcher::new(regex.clone(), insensitive));
+            assert_eq!(
+                collect(matcher(), true),
+                collect(matcher(), false),
+                "pattern {:?}",
+                pattern
+            );
+            assert_eq!(
+                collect(Box::new(InvertedMatcher::new(matcher())), true),
+                collect(Box::new(InvertedMatcher::new(matcher())), false),
+                "inverted pattern {:?}",
+                pattern
+            );
+        }
+
+        for literal in ["lorem", "Ipsum\r", "AMET"] {
+            for insensitive in [false, true] {
+                let matcher = || Box::new(LiteralMatcher::new(literal, insensitive));
+                assert_eq!(collect(matcher(), true), collect(matcher(), false));
+            }
+        }
+
+        // Lines are materialized with their line numbers
+        let regex = Regex::new("^(Ipsum|AMET)$").unwrap();
+        let (lines, _, _) = collect(Box::new(RegexMatcher::new(regex, false)), true);
+        assert_eq!(
+            lines,
+            vec![(2, "Ipsum\r\n".to_string()), (5, "AMET\r\n".to_string())]
+        );
+
+        // Invalid UTF-8 falls back to the line by line search
+        let searcher = Searcher::new(LiteralMatcher::new("b", false));
+        let stats = searcher
+            .search_slice(b"a\xff\nb\n", &mut |_, _: &str| Ok(true))
+            .unwrap();
+        assert_eq!(stats.matched_lines, 1);
+    }
+
 }
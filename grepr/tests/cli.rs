@@ -42,7 +42,7 @@ fn dies_no_args() -> TestResult {
 #[test]
 fn dies_bad_pattern() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&["*foo", FOX])
+        .args(["*foo", FOX])
         .assert()
         .failure()
         .stderr(predicate::str::contains("Invalid pattern \"*foo\""));
@@ -55,7 +55,7 @@ fn warns_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&["foo", &bad])
+        .args(["foo", &bad])
         .assert()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
@@ -72,7 +72,7 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
         expected_file
     };
 
-    let expected = fs::read_to_string(&expected_file)?;
+    let expected = fs::read_to_string(expected_file)?;
 
     Command::cargo_bin(PRG)?
         .args(args)
@@ -234,7 +234,7 @@ fn warns_dir_not_recursive() -> TestResult {
     let stdout = "tests/inputs/fox.txt:\
         The quick brown fox jumps over the lazy dog.";
     Command::cargo_bin(PRG)?
-        .args(&["fox", INPUTS_DIR, FOX])
+        .args(["fox", INPUTS_DIR, FOX])
         .assert()
         .stderr(predicate::str::contains("tests/inputs is a directory"))
         .stdout(predicate::str::contains(stdout));
@@ -271,9 +271,64 @@ fn stdin_insensitive_count() -> TestResult {
     let expected = fs::read_to_string(expected_file)?;
 
     Command::cargo_bin(PRG)?
-        .args(&["-ci", "the", "-"])
+        .args(["-ci", "the", "-"])
         .write_stdin(input)
         .assert()
         .stdout(expected);
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn stats() -> TestResult {
+    let expected =
+        fs::read_to_string("tests/expected/all.the.capitalized")?;
+    let bytes_scanned: u64 = [BUSTLE, EMPTY, FOX, NOBODY]
+        .iter()
+        .map(|file| fs::metadata(file).map(|m| m.len()))
+        .sum::<Result<u64, _>>()?;
+
+    Command::cargo_bin(PRG)?
+        .args(["--stats", "The", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(
+            predicate::str::contains("Files searched:     4")
+                .and(predicate::str::contains("Files with matches: 3"))
+                .and(predicate::str::contains("Matched lines:      5"))
+                .and(predicate::str::contains(format!(
+                    "Bytes scanned:      {}",
+                    bytes_scanned
+                )))
+                .and(predicate::str::contains("Elapsed time:")),
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mmap_multiple_files_insensitive() -> TestResult {
+    run(
+        &["--mmap", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.lowercase.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mmap_insensitive_count_multiple() -> TestResult {
+    run(
+        &["--mmap", "-ic", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.lowercase.insensitive.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn no_mmap_overrides_mmap() -> TestResult {
+    run(
+        &["--mmap", "--no-mmap", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized",
+    )
+}
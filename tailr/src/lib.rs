@@ -1,11 +1,14 @@
 
-use clap::{command, Parser, builder::OsStr};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeDelta};
+use chrono::format::{Parsed, StrftimeItems};
+use clap::{Parser, builder::OsStr};
 use once_cell::sync::OnceCell;
 use regex::Regex;
+use regex::bytes::Regex as BytesRegex;
 
 use crate::Offset::*;
-use std::{error::Error, fs::File, fmt::Debug,
-    io::{BufRead, BufReader, Read, Seek}};
+use std::{collections::VecDeque, error::Error, fs::{self, File, Metadata}, fmt::Debug, thread,
+    io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write}, time::Duration};
 
 pub type MyResult<T> = Result<T, Box<dyn Error>>;
 pub struct MyError {
@@ -31,17 +34,202 @@ impl Error for MyError { }
 enum Offset {
     Start(u64),
     End(u64),
+    /// Lines from the first one stamped at or after the cutoff
+    Since(Cutoff),
 }
 
 impl From<Offset> for OsStr {
     fn from(value: Offset) -> Self {
         match value {
-            Start(num) => OsStr::from(format!("+{}", num.to_string())),
-            End(num) => OsStr::from(num.to_string())
+            Start(num) => OsStr::from(format!("+{}", num)),
+            End(num) => OsStr::from(num.to_string()),
+            Since(cutoff) => OsStr::from(cutoff.time.to_string()),
         }
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+struct Cutoff {
+    time: NaiveDateTime,
+    format: TimestampFormat,
+}
+
+/// Selects lines by --match and --exclude
+#[derive(Debug, Clone, Default)]
+struct LineFilter {
+    matching: Option<BytesRegex>,
+    excluding: Option<BytesRegex>,
+}
+
+impl LineFilter {
+    fn is_active(&self) -> bool {
+        self.matching.is_some() || self.excluding.is_some()
+    }
+
+    /// Tests a line including its delimiter, so that `$` matches at its end
+    fn accepts(&self, line: &[u8], delimiter: u8) -> bool {
+        let line = line.strip_suffix(&[delimiter]).unwrap_or(line);
+        self.matching.as_ref().is_none_or(|regex| { regex.is_match(line) })
+            && !self.excluding.as_ref().is_some_and(|regex| { regex.is_match(line) })
+    }
+}
+
+/// How to find the timestamp of a line: a strftime format matching the
+/// start of the line, or a regex whose first group (or whole match) is a
+/// timestamp in one of the formats `--since` accepts
+#[derive(Debug, Clone)]
+enum TimestampFormat {
+    Strftime(String),
+    Regex(Regex),
+}
+
+impl PartialEq for TimestampFormat {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TimestampFormat::Strftime(a), TimestampFormat::Strftime(b)) => a == b,
+            (TimestampFormat::Regex(a), TimestampFormat::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl Default for TimestampFormat {
+    /// ISO 8601 timestamps at the start of the line, optionally in brackets
+    fn default() -> Self {
+        let regex = DEFAULT_TIMESTAMP_REGEX.get_or_init(|| {
+            Regex::new(r"^\[?(\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(?::\d{2}(?:\.\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?)").unwrap()
+        });
+        TimestampFormat::Regex(regex.clone())
+    }
+}
+
+impl TimestampFormat {
+    fn parse_line(&self, line: &str) -> Option<NaiveDateTime> {
+        match self {
+            TimestampFormat::Strftime(format) => parse_strftime(line, format),
+            TimestampFormat::Regex(regex) => {
+                let captures = regex.captures(line)?;
+                let timestamp = captures.get(1).or_else(|| captures.get(0))?;
+                parse_timestamp(timestamp.as_str())
+            },
+        }
+    }
+}
+
+fn parse_strftime(line: &str, format: &str) -> Option<NaiveDateTime> {
+    let mut parsed = Parsed::new();
+    chrono::format::parse_and_remainder(&mut parsed, line, StrftimeItems::new(format)).ok()?;
+    if let Some(time) = parsed_time(&parsed) {
+        return Some(time);
+    }
+
+    // Formats like syslog's `%b %d %H:%M:%S` have no year, so take the one
+    // that does not put the line in the future
+    if parsed.year().is_some() {
+        return None;
+    }
+    let now = Local::now().naive_local();
+    parsed.set_year(now.year().into()).ok()?;
+    let time = parsed_time(&parsed)?;
+    if time > now + TimeDelta::days(1) {
+        time.with_year(now.year() - 1)
+    } else {
+        Some(time)
+    }
+}
+
+/// Converts times with an offset to local time and takes others as they are
+fn parsed_time(parsed: &Parsed) -> Option<NaiveDateTime> {
+    parsed
+        .to_datetime()
+        .map(|time| { time.with_timezone(&Local).naive_local() })
+        .or_else(|_| { parsed.to_naive_datetime_with_offset(0) })
+        .ok()
+}
+
+static DEFAULT_TIMESTAMP_REGEX: OnceCell<Regex> = OnceCell::new();
+
+const TIMESTAMP_FORMATS: [&str; 5] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+    "%d/%b/%Y:%H:%M:%S",
+];
+
+const TIMESTAMP_FORMATS_WITH_OFFSET: [&str; 3] = [
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    "%d/%b/%Y:%H:%M:%S %z",
+];
+
+/// Parses common log timestamps. Those with a UTC offset are converted to
+/// local time, which is also what timestamps without an offset are taken as.
+fn parse_timestamp(s: &str) -> Option<NaiveDateTime> {
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(time.with_timezone(&Local).naive_local());
+    }
+    for format in TIMESTAMP_FORMATS_WITH_OFFSET {
+        if let Ok(time) = DateTime::parse_from_str(s, format) {
+            return Some(time.with_timezone(&Local).naive_local());
+        }
+    }
+    for format in TIMESTAMP_FORMATS {
+        if let Ok(time) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(time);
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| { date.and_hms_opt(0, 0, 0) })
+}
+
+static DURATION_REGEX: OnceCell<Regex> = OnceCell::new();
+
+/// Accepts a duration like `15m` or `1h30m` before now, or a timestamp
+fn parse_since(s: &str) -> Result<NaiveDateTime, String> {
+    let regex = DURATION_REGEX.get_or_init(
+        || { Regex::new(r"(\d+)([smhdw])").unwrap() });
+
+    let is_duration = !s.is_empty() && regex.replace_all(s, "").is_empty();
+    let time = if is_duration {
+        regex.captures_iter(s)
+            .try_fold(TimeDelta::zero(), |total, captures| {
+                let num: i64 = captures[1].parse().ok()?;
+                let unit = match &captures[2] {
+                    "s" => TimeDelta::try_seconds(num),
+                    "m" => TimeDelta::try_minutes(num),
+                    "h" => TimeDelta::try_hours(num),
+                    "d" => TimeDelta::try_days(num),
+                    _ => TimeDelta::try_weeks(num),
+                };
+                total.checked_add(&unit?)
+            })
+            .and_then(|duration| { Local::now().naive_local().checked_sub_signed(duration) })
+    } else {
+        parse_timestamp(s)
+    };
+    time.ok_or_else(|| format!("invalid duration or timestamp -- {}", s))
+}
+
+fn parse_regex(s: &str) -> Result<BytesRegex, String> {
+    BytesRegex::new(s).map_err(|_| { format!("invalid regex -- {}", s) })
+}
+
+fn parse_timestamp_format(s: &str) -> Result<TimestampFormat, String> {
+    if s.contains('%') {
+        let is_valid = StrftimeItems::new(s).all(|item| { item != chrono::format::Item::Error });
+        // A format that cannot read back the current time lacks a date or time
+        if is_valid && parse_strftime(&Local::now().format(s).to_string(), s).is_some() {
+            return Ok(TimestampFormat::Strftime(s.to_string()));
+        }
+    } else if let Ok(regex) = Regex::new(s) {
+        return Ok(TimestampFormat::Regex(regex));
+    }
+    Err(format!("invalid timestamp format -- {}", s))
+}
+
 fn parse_lines(s: &str) -> Result<Offset, String> {
     match parse_offset(s) {
         Some(offset) => Ok(offset),
@@ -56,12 +244,36 @@ fn parse_bytes(s: &str) -> Result<Offset, String> {
     }
 }
 
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!("invalid number of seconds -- {}", s))
+    }
+}
+
+const BLOCK_SIZE: u64 = 8192;
+
+/// Accepts a single ASCII character or an escape like `\0`, `\t` or `\x1e`
+fn parse_separator(s: &str) -> Result<u8, String> {
+    let separator = match s {
+        "\\0" => Some(b'\0'),
+        "\\n" => Some(b'\n'),
+        "\\r" => Some(b'\r'),
+        "\\t" => Some(b'\t'),
+        _ if s.len() == 1 => Some(s.as_bytes()[0]),
+        _ => s.strip_prefix("\\x")
+            .filter(|hex| hex.len() == 2)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+    };
+    separator.ok_or_else(|| format!("illegal separator -- {}", s))
+}
+
 static OFFSET_REGEX: OnceCell<Regex> = OnceCell::new();
 
 fn parse_offset(s: &str) -> Option<Offset> {
 
     let regex = OFFSET_REGEX.get_or_init(
-        || { Regex::new(r"^([+-])?(\d+)$").unwrap() });
+        || { Regex::new(r"^([+-])?(\d+)(b|[kKMGTPEZY](?:i?B)?)?$").unwrap() });
 
     match regex.captures(s) {
         Some(captures) => {
@@ -70,7 +282,12 @@ fn parse_offset(s: &str) -> Option<Offset> {
                 None => false,
             };
             let num_str = captures.get(2).unwrap().as_str();
-            let num = num_str.parse().unwrap();
+            let multiplier = match captures.get(3) {
+                Some(m) => parse_multiplier(m.as_str())?,
+                None => 1,
+            };
+            // Too large numbers are as illegal as malformed ones
+            let num = num_str.parse::<u64>().ok()?.checked_mul(multiplier)?;
             if from_start {
                 Some(Start(num))
             } else {
@@ -81,6 +298,27 @@ fn parse_offset(s: &str) -> Option<Offset> {
     }
 }
 
+/// Returns the value of a GNU-style suffix like `K` or `KiB` (1024) and `KB`
+/// (1000), or `None` if it does not fit into a u64
+fn parse_multiplier(suffix: &str) -> Option<u64> {
+    if suffix == "b" {
+        return Some(512);
+    }
+
+    let base = if suffix.len() == 2 && suffix.ends_with('B') { 1000u64 } else { 1024 };
+    let exponent = match &suffix[..1] {
+        "k" | "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        "E" => 6,
+        "Z" => 7,
+        _ => 8,
+    };
+    base.checked_pow(exponent)
+}
+
 #[derive(Debug, Parser)]
 #[command(
     author = "Thomas Bollmeier",
@@ -90,9 +328,9 @@ fn parse_offset(s: &str) -> Option<Offset> {
 pub struct Config {
     #[arg(
         value_name = "FILE",
-        help = "Input file(s)",
+        help = "Input file(s), or - for standard input",
         num_args = 1..,
-        required = true
+        default_value = "-"
     )]
     files: Vec<String>,
 
@@ -117,12 +355,145 @@ pub struct Config {
     )]
     bytes: Option<Offset>,
 
+    #[arg(
+        long = "since",
+        group = "mode",
+        value_name = "DURATION|TIMESTAMP",
+        help = "Lines stamped at or after a time, e.g. 15m, 1h30m or \"2024-01-31 12:00\"",
+        value_parser = parse_since
+    )]
+    since: Option<NaiveDateTime>,
+
+    #[arg(
+        long = "timestamp-format",
+        value_name = "FORMAT",
+        help = "With --since, a strftime format at the start of each line, \
+                or a regex capturing the timestamp",
+        value_parser = parse_timestamp_format,
+        requires = "since",
+        conflicts_with_all = ["lines", "bytes"]
+    )]
+    timestamp_format: Option<TimestampFormat>,
+
+    #[arg(
+        long = "match",
+        value_name = "REGEX",
+        help = "Only output lines matching REGEX; -n counts these lines",
+        value_parser = parse_regex,
+        conflicts_with = "bytes"
+    )]
+    matching: Option<BytesRegex>,
+
+    #[arg(
+        long = "exclude",
+        value_name = "REGEX",
+        help = "Do not output lines matching REGEX; -n does not count them",
+        value_parser = parse_regex,
+        conflicts_with = "bytes"
+    )]
+    excluding: Option<BytesRegex>,
+
     #[arg(
         short = 'q',
         long = "quiet",
-        help = "Suppress headers"
+        visible_alias = "silent",
+        help = "Suppress headers",
+        overrides_with = "verbose"
     )]
     quiet: bool,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        help = "Always print headers",
+        overrides_with = "quiet"
+    )]
+    verbose: bool,
+
+    #[arg(
+        short = 'z',
+        long = "zero-terminated",
+        help = "Line delimiter is NUL, not newline",
+        conflicts_with = "separator"
+    )]
+    zero_terminated: bool,
+
+    #[arg(
+        long = "separator",
+        value_name = "BYTE",
+        help = "Line delimiter is BYTE, e.g. ';' or '\\x1e'",
+        value_parser = parse_separator
+    )]
+    separator: Option<u8>,
+
+    #[arg(
+        short = 'f',
+        long = "follow",
+        help = "Output appended data as the file grows"
+    )]
+    follow: bool,
+
+    #[arg(
+        short = 'F',
+        help = "Follow by name, reopening the file after rotation or truncation"
+    )]
+    follow_name: bool,
+
+    #[arg(
+        long = "pid",
+        value_name = "PID",
+        help = "With -f, terminate after process PID dies"
+    )]
+    pid: Option<u32>,
+
+    #[arg(
+        short = 's',
+        long = "sleep-interval",
+        value_name = "N",
+        help = "With -f, sleep N seconds between checks for new data",
+        value_parser = parse_interval,
+        default_value = "1"
+    )]
+    sleep_interval: Duration,
+
+    #[arg(
+        long = "polling",
+        help = "With -f, poll files instead of waiting for file system events"
+    )]
+    polling: bool,
+}
+
+impl Config {
+    fn is_following(&self) -> bool {
+        self.follow || self.follow_name
+    }
+
+    fn line_offset(&self) -> Offset {
+        match self.since {
+            Some(time) => Since(Cutoff {
+                time,
+                format: self.timestamp_format.clone().unwrap_or_default(),
+            }),
+            None => self.lines.clone(),
+        }
+    }
+
+    fn line_filter(&self) -> LineFilter {
+        LineFilter { matching: self.matching.clone(), excluding: self.excluding.clone() }
+    }
+
+    /// Like GNU tail, the last of -q and -v wins
+    fn show_headers(&self) -> bool {
+        self.verbose || (self.files.len() > 1 && !self.quiet)
+    }
+
+    fn delimiter(&self) -> u8 {
+        match (self.zero_terminated, self.separator) {
+            (true, _) => b'\0',
+            (_, Some(separator)) => separator,
+            _ => b'\n',
+        }
+    }
 }
 
 pub fn get_config() -> MyResult<Config> {
@@ -131,90 +502,731 @@ pub fn get_config() -> MyResult<Config> {
 
 pub fn run(config: Config) -> MyResult<()> {
 
-    let multiple_files = config.files.len() > 1;
+    let mut headers = Headers::new(config.show_headers());
+    let mut followed = vec![];
+    let mut has_streams = false;
+
+    if config.pid.is_some() && !config.is_following() {
+        eprintln!("warning: PID ignored; --pid=PID is useful only when following");
+    }
 
     for (file_num, filename) in config.files.iter().enumerate() {
-        match File::open(&filename) {
-            Ok(file) => {
-                if multiple_files && !config.quiet {
-                    println!("{}==> {} <==",
-                        if file_num > 0 { "\n" } else { "" },
-                        filename);
+        // Pipes cannot be followed, so standard input is only printed once
+        if filename == "-" {
+            let mut out = io::stdout().lock();
+            headers.print(&mut out, file_num, "standard input")?;
+            run_stream(io::stdin().lock(), &config, &mut out)?;
+            has_streams = true;
+            continue;
+        }
+
+        match File::open(filename) {
+            Ok(mut file) => {
+                let mut out = io::stdout().lock();
+                headers.print(&mut out, file_num, filename)?;
+                // Like a file that cannot be opened, a read error only ends this file
+                match print_file(&mut file, &config, &mut out) {
+                    Ok(Some(position)) if config.is_following() => {
+                        followed.push(FollowedFile::new(file_num, filename, Some(file), position));
+                    },
+                    Ok(Some(_)) => {},
+                    Ok(None) => has_streams = true,
+                    Err(error) => eprintln!("{}: {}", filename, error),
                 }
-                let (num_lines, num_bytes) =
-                    count_lines_bytes(&filename)?;
-                run_file(file, &config, num_lines, num_bytes);
             }
-            Err(error) => eprintln!("{}: {}", filename, error)
+            Err(error) => {
+                eprintln!("{}: {}", filename, error);
+                if config.follow_name {
+                    followed.push(FollowedFile::new(file_num, filename, None, 0));
+                }
+            }
+        }
+    }
+
+    if config.is_following() {
+        if !followed.is_empty() {
+            follow(&mut followed, &mut headers, &config)?;
+        } else if !has_streams {
+            return Err(Box::new(MyError { error_message: "no files remaining".to_string() }));
         }
     }
 
     Ok(())
 }
 
+/// Prints the tail of an opened file and returns the position to follow it
+/// from. Files that cannot seek, like named pipes, are read as a stream and
+/// cannot be followed, so `None` is returned for them.
+fn print_file(file: &mut File, config: &Config, out: &mut impl Write) -> MyResult<Option<u64>> {
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        run_stream(BufReader::new(file), config, out)?;
+        return Ok(None);
+    }
+
+    let num_bytes = metadata.len();
+    run_file(file, config, num_bytes, out)?;
+    // Following starts at the end, wherever printing left the file: nothing
+    // was read if the offset selected no data, and scanning backward stops
+    // before the end
+    let position = file.stream_position()?.max(num_bytes);
+    file.seek(SeekFrom::Start(position))?;
+    Ok(Some(position))
+}
+
 fn run_file(
-    file: File,
+    file: &mut File,
     config: &Config,
-    num_lines: u64,
-    num_bytes: u64
-) {
+    num_bytes: u64,
+    out: &mut impl Write
+) -> MyResult<()> {
     if let Some(bytes_offset) = &config.bytes {
-        print_bytes(BufReader::new(file), bytes_offset, num_bytes);
+        print_bytes(file, bytes_offset, num_bytes, out)
     } else {
-        print_lines(BufReader::new(file), &config.lines, num_lines);
+        let filter = config.line_filter();
+        match config.line_offset() {
+            End(num) if !filter.is_active() => print_last_lines(file, num, config.delimiter(), out),
+            End(num) => print_last_matching_lines(file, num, config.delimiter(), &filter, out),
+            Since(cutoff) => print_lines_since(file, &cutoff, config.delimiter(), &filter, out),
+            offset => print_stream_lines(BufReader::new(file), &offset, config.delimiter(), &filter, out),
+        }
     }
 }
 
-fn count_lines_bytes(filename: &str) -> MyResult<(u64, u64)> {
+/// Prints the tail of input that cannot seek, such as a pipe, keeping at
+/// most the requested number of lines or bytes in memory
+fn run_stream(reader: impl BufRead, config: &Config, out: &mut impl Write) -> MyResult<()> {
+    if let Some(bytes_offset) = &config.bytes {
+        print_stream_bytes(reader, bytes_offset, out)
+    } else {
+        print_stream_lines(reader, &config.line_offset(), config.delimiter(), &config.line_filter(), out)
+    }
+}
 
-    let file = File::open(&filename).map_err(|e| {
-            Box::new(MyError { error_message: format!("{}", e)})
-        })?;
+fn print_stream_lines(
+    mut reader: impl BufRead,
+    offset: &Offset,
+    delimiter: u8,
+    filter: &LineFilter,
+    out: &mut impl Write
+) -> MyResult<()> {
+    let mut line = vec![];
 
-    let metadata = file.metadata()?;
-    let num_bytes = metadata.len();
+    match offset {
+        Start(num) => {
+            print_lines_from(reader, num.saturating_sub(1), delimiter, filter, out)?;
+        },
+        End(num) => {
+            let mut last_lines: VecDeque<Vec<u8>> = VecDeque::new();
+            while reader.read_until(delimiter, &mut line)? > 0 {
+                if !filter.accepts(&line, delimiter) {
+                    line.clear();
+                } else if last_lines.len() as u64 == *num {
+                    match last_lines.pop_front() {
+                        // Reuse the allocation of the line that drops out
+                        Some(mut oldest) => {
+                            oldest.clear();
+                            std::mem::swap(&mut oldest, &mut line);
+                            last_lines.push_back(oldest);
+                        },
+                        None => line.clear(),
+                    }
+                } else {
+                    last_lines.push_back(std::mem::take(&mut line));
+                }
+            }
+            for line in last_lines {
+                out.write_all(&line)?;
+            }
+        },
+        Since(cutoff) => {
+            // Lines without a timestamp belong to the entry before them
+            while reader.read_until(delimiter, &mut line)? > 0 {
+                if line_time(&line, cutoff, delimiter).is_some_and(|time| { time >= cutoff.time }) {
+                    return print_lines_from(Cursor::new(line).chain(reader), 0, delimiter, filter, out);
+                }
+                line.clear();
+            }
+        },
+    }
+
+    Ok(())
+}
 
-    let num_lines = BufReader::new(file).lines().count() as u64;
+/// Prints the accepted lines from the one with index `start_idx` on
+fn print_lines_from(
+    mut reader: impl BufRead,
+    start_idx: u64,
+    delimiter: u8,
+    filter: &LineFilter,
+    out: &mut impl Write
+) -> MyResult<()> {
+    if start_idx == 0 && !filter.is_active() {
+        io::copy(&mut reader, out)?;
+        return Ok(());
+    }
+
+    let mut line = vec![];
+    let mut idx = 0;
+    while reader.read_until(delimiter, &mut line)? > 0 {
+        if filter.accepts(&line, delimiter) {
+            if idx >= start_idx {
+                out.write_all(&line)?;
+            }
+            idx += 1;
+        }
+        line.clear();
+    }
 
-    Ok((num_lines, num_bytes))
+    Ok(())
 }
 
-fn print_lines(
-    mut file: impl BufRead,
-    offset: &Offset,
-    num_lines: u64
-) {
-    if let Some(start_idx) = get_start_index(offset, num_lines) {
+fn line_time(line: &[u8], cutoff: &Cutoff, delimiter: u8) -> Option<NaiveDateTime> {
+    let line = line.strip_suffix(&[delimiter]).unwrap_or(line);
+    cutoff.format.parse_line(&String::from_utf8_lossy(line))
+}
 
-        let mut line = String::new();
-        let mut idx = 0;
+/// Returns the start and time of the first stamped line that starts at or
+/// after `pos`, if any
+fn next_stamped_line(
+    reader: &mut (impl BufRead + Seek),
+    pos: u64,
+    cutoff: &Cutoff,
+    delimiter: u8
+) -> io::Result<Option<(u64, NaiveDateTime)>> {
+    let mut line = vec![];
+    let mut line_start = pos;
 
-        while let Ok(size) = file.read_line(&mut line) {
-            if size == 0 {
+    // Skip the rest of the line that `pos` falls into
+    if pos > 0 {
+        reader.seek(SeekFrom::Start(pos - 1))?;
+        line_start = pos - 1 + reader.read_until(delimiter, &mut line)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    loop {
+        line.clear();
+        let len = reader.read_until(delimiter, &mut line)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        if let Some(time) = line_time(&line, cutoff, delimiter) {
+            return Ok(Some((line_start, time)));
+        }
+        line_start += len as u64;
+    }
+}
+
+/// Binary searches for the first line stamped at or after the cutoff,
+/// assuming that the timestamps in the file never decrease
+fn find_since_start(
+    file: &mut (impl Read + Seek),
+    cutoff: &Cutoff,
+    delimiter: u8
+) -> io::Result<u64> {
+    let size = file.seek(SeekFrom::End(0))?;
+    let mut reader = BufReader::new(file);
+    let (mut low, mut high) = (0, size);
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match next_stamped_line(&mut reader, mid, cutoff, delimiter)? {
+            // Every position up to that line leads to the same early line
+            Some((line_start, time)) if time < cutoff.time => low = line_start + 1,
+            _ => high = mid,
+        }
+    }
+
+    Ok(next_stamped_line(&mut reader, low, cutoff, delimiter)?
+        .map_or(size, |(line_start, _)| { line_start }))
+}
+
+fn print_lines_since(
+    file: &mut (impl Read + Seek),
+    cutoff: &Cutoff,
+    delimiter: u8,
+    filter: &LineFilter,
+    out: &mut impl Write
+) -> MyResult<()> {
+    let start = find_since_start(file, cutoff, delimiter)?;
+    file.seek(SeekFrom::Start(start))?;
+    print_lines_from(BufReader::new(file), 0, delimiter, filter, out)
+}
+
+fn print_stream_bytes(mut reader: impl BufRead, offset: &Offset, out: &mut impl Write) -> MyResult<()> {
+    match offset {
+        Start(num) => {
+            io::copy(&mut reader.by_ref().take(num.saturating_sub(1)), &mut io::sink())?;
+            io::copy(&mut reader, out)?;
+        },
+        End(num) => {
+            let limit = usize::try_from(*num).unwrap_or(usize::MAX);
+            let mut last_bytes: VecDeque<u8> = VecDeque::new();
+            loop {
+                let buf = reader.fill_buf()?;
+                if buf.is_empty() {
+                    break;
+                }
+                let len = buf.len();
+                let keep = &buf[len.saturating_sub(limit)..];
+                let overflow = (last_bytes.len() + keep.len()).saturating_sub(limit);
+                last_bytes.drain(..overflow);
+                last_bytes.extend(keep);
+                reader.consume(len);
+            }
+            let (front, back) = last_bytes.as_slices();
+            out.write_all(front)?;
+            out.write_all(back)?;
+        },
+        Since(_) => unreachable!("--since cannot be used with --bytes"),
+    }
+
+    Ok(())
+}
+
+/// Prints the `==> FILE <==` headers whenever the output switches files
+struct Headers {
+    enabled: bool,
+    last_file_num: Option<usize>,
+}
+
+impl Headers {
+    fn new(enabled: bool) -> Self {
+        Headers { enabled, last_file_num: None }
+    }
+
+    fn print(&mut self, out: &mut impl Write, file_num: usize, filename: &str) -> io::Result<()> {
+        if self.enabled && self.last_file_num != Some(file_num) {
+            writeln!(out, "{}==> {} <==",
+                if self.last_file_num.is_some() { "\n" } else { "" },
+                filename)?;
+        }
+        self.last_file_num = Some(file_num);
+        Ok(())
+    }
+}
+
+/// A file whose appended data is printed in follow mode. The file is `None`
+/// while a file followed by name is missing.
+struct FollowedFile {
+    file_num: usize,
+    filename: String,
+    file: Option<File>,
+    position: u64,
+    /// The unfinished last line when lines are filtered
+    pending: Vec<u8>,
+}
+
+impl FollowedFile {
+    fn new(file_num: usize, filename: &str, file: Option<File>, position: u64) -> Self {
+        FollowedFile { file_num, filename: filename.to_string(), file, position, pending: vec![] }
+    }
+
+    /// Prints everything written since the last call, starting over if the
+    /// file was truncated. With an active filter only complete lines that
+    /// pass it are printed.
+    fn print_new_data(
+        &mut self,
+        out: &mut impl Write,
+        headers: &mut Headers,
+        filter: &LineFilter,
+        delimiter: u8
+    ) -> MyResult<()> {
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+
+        if file.metadata()?.len() < self.position {
+            eprintln!("{}: file truncated", self.filename);
+            self.position = file.seek(SeekFrom::Start(0))?;
+            self.pending.clear();
+        }
+
+        let mut buf = [0; 8192];
+        loop {
+            let num_read = file.read(&mut buf)?;
+            if num_read == 0 {
                 break;
             }
-            if idx >= start_idx {
-                print!("{}", line);
+            self.position += num_read as u64;
+            if !filter.is_active() {
+                headers.print(out, self.file_num, &self.filename)?;
+                out.write_all(&buf[..num_read])?;
+                continue;
+            }
+
+            self.pending.extend_from_slice(&buf[..num_read]);
+            let mut start = 0;
+            while let Some(len) = self.pending[start..].iter().position(|&b| b == delimiter) {
+                let line = &self.pending[start..start + len + 1];
+                if filter.accepts(line, delimiter) {
+                    headers.print(out, self.file_num, &self.filename)?;
+                    out.write_all(line)?;
+                }
+                start += len + 1;
+            }
+            self.pending.drain(..start);
+        }
+        out.flush()?;
+
+        Ok(())
+    }
+
+    /// Prints the unfinished last line if it passes the filter
+    fn print_pending(
+        &mut self,
+        out: &mut impl Write,
+        headers: &mut Headers,
+        filter: &LineFilter,
+        delimiter: u8
+    ) -> MyResult<()> {
+        if !self.pending.is_empty() && filter.accepts(&self.pending, delimiter) {
+            headers.print(out, self.file_num, &self.filename)?;
+            out.write_all(&self.pending)?;
+            out.flush()?;
+        }
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Switches to a new file after the old one was renamed or removed,
+    /// printing the rest of the old file first
+    fn reopen_if_replaced(
+        &mut self,
+        out: &mut impl Write,
+        headers: &mut Headers,
+        filter: &LineFilter,
+        delimiter: u8
+    ) -> MyResult<()> {
+        match fs::metadata(&self.filename) {
+            Ok(metadata) => {
+                let replaced = match &self.file {
+                    Some(file) => file_id(&file.metadata()?) != file_id(&metadata),
+                    None => true,
+                };
+                if !replaced {
+                    return Ok(());
+                }
+                if let Ok(file) = File::open(&self.filename) {
+                    if self.file.is_some() {
+                        self.print_new_data(out, headers, filter, delimiter)?;
+                        self.print_pending(out, headers, filter, delimiter)?;
+                        eprintln!("'{}' has been replaced;  following new file", self.filename);
+                    } else {
+                        eprintln!("'{}' has appeared;  following new file", self.filename);
+                    }
+                    self.file = Some(file);
+                    self.position = 0;
+                }
+            },
+            Err(error) => {
+                if self.file.is_some() {
+                    self.print_new_data(out, headers, filter, delimiter)?;
+                    self.print_pending(out, headers, filter, delimiter)?;
+                    eprintln!("'{}' has become inaccessible: {}", self.filename, error);
+                    self.file = None;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+fn follow(files: &mut [FollowedFile], headers: &mut Headers, config: &Config) -> MyResult<()> {
+    let mut out = io::stdout();
+    let mut watcher = new_watcher(config);
+    let filter = config.line_filter();
+    let delimiter = config.delimiter();
+
+    loop {
+        // Check before reading so that the last output of the process is printed
+        let process_alive = config.pid.is_none_or(is_process_alive);
+
+        for file in files.iter_mut() {
+            if config.follow_name {
+                file.reopen_if_replaced(&mut out, headers, &filter, delimiter)?;
+            }
+            file.print_new_data(&mut out, headers, &filter, delimiter)?;
+        }
+
+        if !process_alive {
+            // The process will not finish the last lines, so print them as they are
+            for file in files.iter_mut() {
+                file.print_pending(&mut out, headers, &filter, delimiter)?;
+            }
+            return Ok(());
+        }
+        watcher.wait(files)?;
+    }
+}
+
+/// Blocks until the followed files may have changed. Spurious wakeups are
+/// fine as every file is checked afterwards anyway.
+trait Watcher {
+    fn wait(&mut self, files: &[FollowedFile]) -> MyResult<()>;
+}
+
+fn new_watcher(config: &Config) -> Box<dyn Watcher> {
+    #[cfg(target_os = "linux")]
+    if !config.polling {
+        match InotifyWatcher::new(config) {
+            Ok(watcher) => return Box::new(watcher),
+            Err(error) => eprintln!("inotify cannot be used, reverting to polling: {}", error),
+        }
+    }
+
+    Box::new(PollingWatcher { interval: config.sleep_interval })
+}
+
+/// Works everywhere, including on network file systems
+struct PollingWatcher {
+    interval: Duration,
+}
+
+impl Watcher for PollingWatcher {
+    fn wait(&mut self, _files: &[FollowedFile]) -> MyResult<()> {
+        thread::sleep(self.interval);
+        Ok(())
+    }
+}
+
+/// Wakes up as soon as a followed file changes, but at least once per sleep
+/// interval to check on --pid
+#[cfg(target_os = "linux")]
+struct InotifyWatcher {
+    inotify: inotify::Inotify,
+    interval: Duration,
+    follow_name: bool,
+    buffer: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+impl InotifyWatcher {
+    fn new(config: &Config) -> io::Result<Self> {
+        Ok(InotifyWatcher {
+            inotify: inotify::Inotify::init()?,
+            interval: config.sleep_interval,
+            follow_name: config.follow_name,
+            buffer: vec![0; 4096],
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher for InotifyWatcher {
+    fn wait(&mut self, files: &[FollowedFile]) -> MyResult<()> {
+        use inotify::WatchMask;
+        use std::{os::fd::AsRawFd, path::Path};
+
+        // Adding a watch again is cheap and picks up files that were
+        // replaced, while watches on rotated files stay in place
+        for file in files {
+            let _ = self.inotify.watches().add(&file.filename,
+                WatchMask::MODIFY | WatchMask::ATTRIB | WatchMask::DELETE_SELF | WatchMask::MOVE_SELF);
+            if self.follow_name {
+                let dir = match Path::new(&file.filename).parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                let _ = self.inotify.watches().add(dir,
+                    WatchMask::CREATE | WatchMask::MOVED_TO | WatchMask::DELETE | WatchMask::MOVED_FROM);
+            }
+        }
+
+        let mut pollfd = libc::pollfd { fd: self.inotify.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        let timeout = self.interval.as_millis().min(i32::MAX as u128) as i32;
+        if unsafe { libc::poll(&mut pollfd, 1, timeout) } < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(Box::new(error));
+            }
+        }
+
+        // Only the wakeup matters, so the events are just drained
+        match self.inotify.read_events(&mut self.buffer) {
+            Ok(_) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(error) => Err(Box::new(error)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+    // Signal 0 performs the error checking of kill() without sending anything
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Yields the bytes of a seekable file with their positions, from `end`
+/// backward to the start. The file is read in blocks, so that scanning the
+/// tail of a large file only reads the tail.
+struct BackwardBytes<'a, F> {
+    file: &'a mut F,
+    block: Vec<u8>,
+    block_start: u64,
+    block_size: u64,
+    idx: usize,
+}
+
+impl<'a, F: Read + Seek> BackwardBytes<'a, F> {
+    fn new(file: &'a mut F, end: u64, block_size: u64) -> Self {
+        BackwardBytes { file, block: vec![], block_start: end, block_size, idx: 0 }
+    }
+
+    fn read_previous_block(&mut self) -> io::Result<()> {
+        let block_end = self.block_start;
+        self.block_start = block_end.saturating_sub(self.block_size);
+        self.block.resize((block_end - self.block_start) as usize, 0);
+        self.file.seek(SeekFrom::Start(self.block_start))?;
+        self.file.read_exact(&mut self.block)?;
+        self.idx = self.block.len();
+        Ok(())
+    }
+}
+
+impl<F: Read + Seek> Iterator for BackwardBytes<'_, F> {
+    type Item = io::Result<(u64, u8)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx == 0 {
+            if self.block_start == 0 {
+                return None;
+            }
+            if let Err(error) = self.read_previous_block() {
+                self.block_start = 0;
+                return Some(Err(error));
+            }
+        }
+        self.idx -= 1;
+        Some(Ok((self.block_start + self.idx as u64, self.block[self.idx])))
+    }
+}
+
+/// Finds where the last `num` lines of a seekable file start by scanning
+/// backward from the end
+fn find_last_lines_start(
+    file: &mut (impl Read + Seek),
+    num: u64,
+    delimiter: u8,
+    block_size: u64
+) -> io::Result<u64> {
+    let size = file.seek(SeekFrom::End(0))?;
+    if num == 0 {
+        return Ok(size);
+    }
+
+    let mut num_newlines = 0;
+    for byte in BackwardBytes::new(file, size, block_size) {
+        let (pos, byte) = byte?;
+        // The delimiter that ends the last line does not start another one
+        if byte == delimiter && pos + 1 < size {
+            num_newlines += 1;
+            if num_newlines == num {
+                return Ok(pos + 1);
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+fn print_last_lines(
+    file: &mut (impl Read + Seek),
+    num: u64,
+    delimiter: u8,
+    out: &mut impl Write
+) -> MyResult<()> {
+    let start = find_last_lines_start(file, num, delimiter, BLOCK_SIZE)?;
+    file.seek(SeekFrom::Start(start))?;
+    io::copy(file, out)?;
+    Ok(())
+}
+
+/// Collects the last `num` lines that pass the filter, scanning backward
+/// from the end like `find_last_lines_start`
+fn find_last_matching_lines(
+    file: &mut (impl Read + Seek),
+    num: u64,
+    delimiter: u8,
+    filter: &LineFilter,
+    block_size: u64
+) -> io::Result<Vec<Vec<u8>>> {
+    let size = file.seek(SeekFrom::End(0))?;
+    let mut lines = vec![];
+    if num == 0 {
+        return Ok(lines);
+    }
+
+    // The bytes of the current line in reverse order
+    let mut line = vec![];
+    for byte in BackwardBytes::new(file, size, block_size) {
+        let (pos, byte) = byte?;
+        if byte == delimiter && pos + 1 < size {
+            line.reverse();
+            if filter.accepts(&line, delimiter) {
+                lines.push(std::mem::take(&mut line));
+                if lines.len() as u64 == num {
+                    lines.reverse();
+                    return Ok(lines);
+                }
             }
-            idx += 1;
             line.clear();
         }
+        line.push(byte);
+    }
+
+    line.reverse();
+    if !line.is_empty() && filter.accepts(&line, delimiter) {
+        lines.push(line);
     }
+    lines.reverse();
+    Ok(lines)
 }
 
-fn print_bytes<T: Read + Seek>(
-    mut file : T,
+fn print_last_matching_lines(
+    file: &mut (impl Read + Seek),
+    num: u64,
+    delimiter: u8,
+    filter: &LineFilter,
+    out: &mut impl Write
+) -> MyResult<()> {
+    for line in find_last_matching_lines(file, num, delimiter, filter, BLOCK_SIZE)? {
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// Copies the bytes unchanged, so that binary data and offsets inside a
+/// multibyte character come out as they are in the file
+fn print_bytes(
+    file: &mut (impl Read + Seek),
     offset: &Offset,
-    num_bytes: u64
-) {
+    num_bytes: u64,
+    out: &mut impl Write
+) -> MyResult<()> {
     if let Some(start_idx) = get_start_index(offset, num_bytes)  {
-        file.seek(std::io::SeekFrom::Start(start_idx)).expect("seek failed");
-        let mut bytes: Vec<u8> = vec![];
-        if let Ok(_) = file.read_to_end(&mut bytes) {
-            let content = String::from_utf8_lossy(&bytes);
-            print!("{}", content);
-        }
+        file.seek(SeekFrom::Start(start_idx))?;
+        io::copy(file, out)?;
     }
+    Ok(())
 }
 
 fn get_start_index(offset: &Offset, size: u64) -> Option<u64> {
@@ -238,6 +1250,7 @@ fn get_start_index(offset: &Offset, size: u64) -> Option<u64> {
             };
             Some(start_idx)
         },
+        Since(_) => unreachable!("--since cannot be used with --bytes"),
     }
 }
 
@@ -247,19 +1260,61 @@ fn get_start_index(offset: &Offset, size: u64) -> Option<u64> {
 #[cfg(test)]
 mod tests {
     use super::{
-        parse_offset, Offset::*, count_lines_bytes, get_start_index,
+        parse_offset, parse_separator, parse_since, parse_timestamp, parse_timestamp_format,
+        find_since_start, Cutoff, LineFilter, TimestampFormat, Headers, Offset::*, get_start_index, find_last_lines_start,
+        find_last_matching_lines, print_stream_lines, print_stream_bytes, BytesRegex,
     };
+    use chrono::{Datelike, Local, NaiveDateTime, TimeDelta, Timelike};
+    use std::fs::File;
+    use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 
 
     #[test]
-    fn test_count_lines_bytes() {
-        let res = count_lines_bytes("tests/inputs/one.txt");
-        assert!(res.is_ok());
-        assert_eq!(res.unwrap(), (1, 24));
+    fn test_find_last_lines_start() {
+        let mut file = File::open("tests/inputs/one.txt").unwrap();
+        assert_eq!(find_last_lines_start(&mut file, 1, b'\n', 8192).unwrap(), 0);
+        assert_eq!(find_last_lines_start(&mut file, 0, b'\n', 8192).unwrap(), 24);
 
-        let res = count_lines_bytes("tests/inputs/ten.txt");
-        assert!(res.is_ok());
-        assert_eq!(res.unwrap(), (10, 49));
+        // Tiny blocks make the newlines fall on block boundaries
+        let mut text = Cursor::new("one\ntwo\nthree\n");
+        assert_eq!(find_last_lines_start(&mut text, 1, b'\n', 2).unwrap(), 8);
+        assert_eq!(find_last_lines_start(&mut text, 2, b'\n', 3).unwrap(), 4);
+        assert_eq!(find_last_lines_start(&mut text, 3, b'\n', 1).unwrap(), 0);
+        assert_eq!(find_last_lines_start(&mut text, 4, b'\n', 4).unwrap(), 0);
+
+        // Without a final newline the last line is still a line
+        let mut text = Cursor::new("one\ntwo");
+        assert_eq!(find_last_lines_start(&mut text, 1, b'\n', 3).unwrap(), 4);
+
+        let mut text = Cursor::new("\n\n");
+        assert_eq!(find_last_lines_start(&mut text, 1, b'\n', 1).unwrap(), 1);
+        assert_eq!(find_last_lines_start(&mut text, 2, b'\n', 1).unwrap(), 0);
+
+        // Other delimiters make newlines ordinary bytes
+        let mut text = Cursor::new("a\nb\0c\nd\0");
+        assert_eq!(find_last_lines_start(&mut text, 1, b'\0', 2).unwrap(), 4);
+        assert_eq!(find_last_lines_start(&mut text, 2, b'\0', 2).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_last_lines_match_stream() {
+        // Seeking backward must print what reading forward would
+        for filename in ["empty.txt", "one.txt", "two.txt", "three.txt", "ten.txt"] {
+            let path = format!("tests/inputs/{}", filename);
+            for num in 0..12 {
+                let mut expected = vec![];
+                let reader = BufReader::new(File::open(&path).unwrap());
+                print_stream_lines(reader, &End(num), b'\n', &LineFilter::default(), &mut expected).unwrap();
+
+                let mut file = File::open(&path).unwrap();
+                let start = find_last_lines_start(&mut file, num, b'\n', 4).unwrap();
+                let mut actual = vec![];
+                file.seek(SeekFrom::Start(start)).unwrap();
+                file.read_to_end(&mut actual).unwrap();
+
+                assert_eq!(actual, expected, "{} -n {}", filename, num);
+            }
+        }
     }
 
     #[test]
@@ -340,5 +1395,263 @@ mod tests {
         // Any non-integer string is invalid
         let res = parse_offset("foo");
         assert!(res.is_none());
+
+        // Multiplier suffixes
+        assert_eq!(parse_offset("2b"), Some(End(1024)));
+        assert_eq!(parse_offset("3K"), Some(End(3072)));
+        assert_eq!(parse_offset("3k"), Some(End(3072)));
+        assert_eq!(parse_offset("+1KiB"), Some(Start(1024)));
+        assert_eq!(parse_offset("1KB"), Some(End(1000)));
+        assert_eq!(parse_offset("1kB"), Some(End(1000)));
+        assert_eq!(parse_offset("-5M"), Some(End(5 * 1024 * 1024)));
+        assert_eq!(parse_offset("5MB"), Some(End(5_000_000)));
+        assert_eq!(parse_offset("2MiB"), Some(End(2 * 1024 * 1024)));
+        assert_eq!(parse_offset("1G"), Some(End(1 << 30)));
+        assert_eq!(parse_offset("1GB"), Some(End(1_000_000_000)));
+        assert_eq!(parse_offset("1T"), Some(End(1 << 40)));
+        assert_eq!(parse_offset("1P"), Some(End(1 << 50)));
+        assert_eq!(parse_offset("15E"), Some(End(15 << 60)));
+        assert!(parse_offset("3X").is_none());
+        assert!(parse_offset("3iB").is_none());
+        assert!(parse_offset("3Kb").is_none());
+
+        // Overflow is invalid
+        assert!(parse_offset("16E").is_none());
+        assert!(parse_offset("1Z").is_none());
+        assert!(parse_offset("1Y").is_none());
+        assert!(parse_offset(&format!("{}0", u64::MAX)).is_none());
+        assert!(parse_offset(&format!("{}K", u64::MAX)).is_none());
+    }
+
+    #[test]
+    fn test_print_stream() {
+        let text = "one\ntwo\nthree\nfour";
+        // A tiny buffer splits the input into many chunks
+        let reader = || { BufReader::with_capacity(3, Cursor::new(text)) };
+
+        let mut out = vec![];
+        print_stream_lines(reader(), &End(2), b'\n', &LineFilter::default(), &mut out).unwrap();
+        assert_eq!(out, b"three\nfour");
+
+        let mut out = vec![];
+        print_stream_lines(reader(), &End(0), b'\n', &LineFilter::default(), &mut out).unwrap();
+        assert_eq!(out, b"");
+
+        let mut out = vec![];
+        print_stream_lines(reader(), &Start(3), b'\n', &LineFilter::default(), &mut out).unwrap();
+        assert_eq!(out, b"three\nfour");
+
+        let mut out = vec![];
+        print_stream_lines(reader(), &End(2), b'e', &LineFilter::default(), &mut out).unwrap();
+        assert_eq!(out, b"e\nfour");
+
+        let mut out = vec![];
+        print_stream_bytes(reader(), &End(7), &mut out).unwrap();
+        assert_eq!(out, b"ee\nfour");
+
+        let mut out = vec![];
+        print_stream_bytes(reader(), &End(u64::MAX), &mut out).unwrap();
+        assert_eq!(out, text.as_bytes());
+
+        let mut out = vec![];
+        print_stream_bytes(reader(), &Start(9), &mut out).unwrap();
+        assert_eq!(out, b"three\nfour");
+    }
+
+    #[test]
+    fn test_line_filter() {
+        let filter = LineFilter {
+            matching: Some(BytesRegex::new("^[a-z]+o$").unwrap()),
+            excluding: Some(BytesRegex::new("w").unwrap()),
+        };
+        assert!(filter.is_active());
+        assert!(!LineFilter::default().is_active());
+        assert!(filter.accepts(b"zero\n", b'\n'));
+        assert!(filter.accepts(b"zero", b'\n'));
+        assert!(!filter.accepts(b"two\n", b'\n'));
+        assert!(!filter.accepts(b"three\n", b'\n'));
+
+        // -n counts the lines that pass the filter
+        let text = "zero\none\ntwo\nthree\nfour\nhello";
+        let filter = LineFilter { excluding: Some(BytesRegex::new("^t").unwrap()), ..Default::default() };
+        let reader = || { BufReader::with_capacity(3, Cursor::new(text)) };
+
+        let mut out = vec![];
+        print_stream_lines(reader(), &End(2), b'\n', &filter, &mut out).unwrap();
+        assert_eq!(out, b"four\nhello");
+
+        let mut out = vec![];
+        print_stream_lines(reader(), &Start(3), b'\n', &filter, &mut out).unwrap();
+        assert_eq!(out, b"four\nhello");
+
+        // Scanning backward must find what reading forward does
+        let filters = [
+            filter,
+            LineFilter { matching: Some(BytesRegex::new("e").unwrap()), ..Default::default() },
+            LineFilter { matching: Some(BytesRegex::new("^$").unwrap()), ..Default::default() },
+        ];
+        for filename in ["empty.txt", "one.txt", "three.txt", "ten.txt", "zero.txt"] {
+            let path = format!("tests/inputs/{}", filename);
+            for filter in &filters {
+                for num in 0..12 {
+                    let mut expected = vec![];
+                    let reader = BufReader::new(File::open(&path).unwrap());
+                    print_stream_lines(reader, &End(num), b'\n', filter, &mut expected).unwrap();
+
+                    let mut file = File::open(&path).unwrap();
+                    let actual = find_last_matching_lines(&mut file, num, b'\n', filter, 4).unwrap();
+                    assert_eq!(actual.concat(), expected, "{} {:?} -n {}", filename, filter, num);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_separator() {
+        assert_eq!(parse_separator(";"), Ok(b';'));
+        assert_eq!(parse_separator("\\0"), Ok(b'\0'));
+        assert_eq!(parse_separator("\\t"), Ok(b'\t'));
+        assert_eq!(parse_separator("\\x1e"), Ok(0x1e));
+        assert_eq!(parse_separator("\\x1"), Err("illegal separator -- \\x1".to_string()));
+        assert!(parse_separator("").is_err());
+        assert!(parse_separator(";;").is_err());
+        assert!(parse_separator("é").is_err());
+    }
+
+    #[test]
+    fn test_headers() {
+        let mut out = vec![];
+        let mut headers = Headers::new(true);
+        headers.print(&mut out, 0, "a").unwrap();
+        headers.print(&mut out, 0, "a").unwrap();
+        headers.print(&mut out, 1, "b").unwrap();
+        headers.print(&mut out, 0, "a").unwrap();
+        assert_eq!(out, b"==> a <==\n\n==> b <==\n\n==> a <==\n");
+
+        let mut out = vec![];
+        let mut headers = Headers::new(false);
+        headers.print(&mut out, 0, "a").unwrap();
+        headers.print(&mut out, 1, "b").unwrap();
+        assert_eq!(out, b"");
+    }
+
+    fn time(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("2024-01-31 10:00"), Ok(time("2024-01-31 10:00:00")));
+        assert_eq!(parse_since("2024-01-31T10:00:05"), Ok(time("2024-01-31 10:00:05")));
+        assert_eq!(parse_since("2024-01-31"), Ok(time("2024-01-31 00:00:00")));
+
+        // Durations count back from now
+        let expected = Local::now().naive_local() - TimeDelta::minutes(90);
+        let since = parse_since("1h30m").unwrap();
+        assert!((since - expected).abs() < TimeDelta::seconds(5));
+        let since = parse_since("15m").unwrap();
+        assert!(since > expected);
+
+        assert!(parse_since("").is_err());
+        assert!(parse_since("15").is_err());
+        assert!(parse_since("15x").is_err());
+        assert!(parse_since("m15").is_err());
+        assert!(parse_since("99999999999999w").is_err());
+        assert_eq!(
+            parse_since("yesterday").unwrap_err(),
+            "invalid duration or timestamp -- yesterday"
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("2024-01-31 10:00:00.250"),
+            Some(time("2024-01-31 10:00:00") + TimeDelta::milliseconds(250)));
+        assert_eq!(parse_timestamp("31/Jan/2024:10:00:00"), Some(time("2024-01-31 10:00:00")));
+        assert!(parse_timestamp("2024-01-31T10:00:00Z").is_some());
+        assert!(parse_timestamp("31/Jan/2024:10:00:00 +0100").is_some());
+        assert!(parse_timestamp("2024-13-01").is_none());
+        assert!(parse_timestamp("INFO").is_none());
+    }
+
+    #[test]
+    fn test_timestamp_format() {
+        let format = TimestampFormat::default();
+        assert_eq!(format.parse_line("2024-01-31 10:00:00 INFO"), Some(time("2024-01-31 10:00:00")));
+        assert_eq!(format.parse_line("[2024-01-31T10:00:00] INFO"), Some(time("2024-01-31 10:00:00")));
+        assert_eq!(format.parse_line("  at main (main.rs:3)"), None);
+
+        let format = parse_timestamp_format("%b %d %Y %H:%M:%S").unwrap();
+        assert_eq!(format.parse_line("Jan 31 2024 10:00:00 host sshd"), Some(time("2024-01-31 10:00:00")));
+        assert_eq!(format.parse_line("host Jan 31 2024 10:00:00"), None);
+
+        let format = parse_timestamp_format(r"time=(\S+)").unwrap();
+        assert_eq!(format.parse_line("level=info time=2024-01-31T10:00:00"), Some(time("2024-01-31 10:00:00")));
+
+        // Without a year, the latest one that is not in the future is taken
+        let format = parse_timestamp_format("%b %d %H:%M:%S").unwrap();
+        let now = Local::now().naive_local();
+        let yesterday = now - TimeDelta::days(1);
+        let line = format!("{} host sshd", yesterday.format("%b %d %H:%M:%S"));
+        assert_eq!(format.parse_line(&line), Some(yesterday.with_nanosecond(0).unwrap()));
+        let next_week = now + TimeDelta::days(7);
+        let line = format!("{} host sshd", next_week.format("%b %d %H:%M:%S"));
+        assert_eq!(format.parse_line(&line).map(|time| { time.year() }), Some(next_week.year() - 1));
+
+        assert!(parse_timestamp_format("%Y-%m-%d %Q").is_err());
+        assert!(parse_timestamp_format("%H:%M:%S").is_err());
+        assert_eq!(parse_timestamp_format("(").unwrap_err(), "invalid timestamp format -- (");
+    }
+
+    #[test]
+    fn test_find_since_start() {
+        let log = "2024-01-31 09:00:00 a\n\
+                   2024-01-31 10:00:00 b\n\
+                   \x20 continued\n\
+                   2024-01-31 10:00:00 c\n\
+                   2024-01-31 11:00:00 d";
+        let cutoffs = [
+            ("2024-01-31 08:00:00", 0),
+            ("2024-01-31 09:00:00", 0),
+            ("2024-01-31 09:00:01", 22),
+            ("2024-01-31 10:00:00", 22),
+            ("2024-01-31 10:30:00", 78),
+            ("2024-01-31 11:00:01", log.len()),
+        ];
+        for (cutoff, expected) in cutoffs {
+            let cutoff = Cutoff { time: time(cutoff), format: TimestampFormat::default() };
+
+            // The result must not depend on where the search lands first
+            let mut stream = vec![];
+            print_stream_lines(Cursor::new(log), &Since(cutoff.clone()), b'\n', &LineFilter::default(), &mut stream).unwrap();
+            let start = find_since_start(&mut Cursor::new(log), &cutoff, b'\n').unwrap();
+            assert_eq!(start, expected as u64, "{:?}", cutoff.time);
+            assert_eq!(&log.as_bytes()[start as usize..], stream.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_find_since_start_many_lines() {
+        // Repeated timestamps and runs of unstamped lines of varying length
+        let mut log = String::new();
+        for minute in 0..300 {
+            let stamp = time("2024-01-31 00:00:00") + TimeDelta::minutes(minute / 2);
+            log.push_str(&format!("{} entry {}\n", stamp, minute));
+            for line in 0..(minute % 4) {
+                log.push_str(&format!("  detail {}\n", line));
+            }
+        }
+
+        for minute in -1..152 {
+            let cutoff = Cutoff {
+                time: time("2024-01-31 00:00:00") + TimeDelta::minutes(minute),
+                format: TimestampFormat::default(),
+            };
+            let mut stream = vec![];
+            print_stream_lines(Cursor::new(&log), &Since(cutoff.clone()), b'\n',
+                &LineFilter::default(), &mut stream).unwrap();
+            let start = find_since_start(&mut Cursor::new(&log), &cutoff, b'\n').unwrap();
+            assert_eq!(&log.as_bytes()[start as usize..], stream.as_slice(), "{}", minute);
+        }
     }
 }
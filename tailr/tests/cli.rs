@@ -1,8 +1,13 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::fs::{self, File};
-use std::io::Read;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Command as StdCommand;
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -12,6 +17,10 @@ const ONE: &str = "tests/inputs/one.txt";
 const TWO: &str = "tests/inputs/two.txt";
 const THREE: &str = "tests/inputs/three.txt";
 const TEN: &str = "tests/inputs/ten.txt";
+const ZERO: &str = "tests/inputs/zero.txt";
+const BINARY: &str = "tests/inputs/binary.bin";
+const APP_LOG: &str = "tests/inputs/app.log";
+const ACCESS_LOG: &str = "tests/inputs/access.log";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -34,11 +43,12 @@ fn gen_bad_file() -> String {
 
 // --------------------------------------------------
 #[test]
-fn dies_no_args() -> TestResult {
+fn reads_stdin_without_args() -> TestResult {
     Command::cargo_bin(PRG)?
+        .pipe_stdin(TEN)?
         .assert()
-        .failure()
-        .stderr(predicate::str::contains("Usage"));
+        .success()
+        .stdout(fs::read_to_string("tests/expected/ten.txt.out")?);
 
     Ok(())
 }
@@ -49,7 +59,7 @@ fn dies_bad_bytes() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal byte count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-c", &bad, EMPTY])
+        .args(["-c", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -63,7 +73,7 @@ fn dies_bad_lines() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal line count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-n", &bad, EMPTY])
+        .args(["-n", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -78,7 +88,7 @@ fn dies_bytes_and_lines() -> TestResult {
                used with '--bytes <BYTES>'";
 
     Command::cargo_bin(PRG)?
-        .args(&["-n", "1", "-c", "2"])
+        .args(["-n", "1", "-c", "2"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(msg));
@@ -86,31 +96,76 @@ fn dies_bytes_and_lines() -> TestResult {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_bytes_overflow() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "16E", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal byte count -- 16E"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_lines_overflow() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "99999999999999999999", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "illegal line count -- 99999999999999999999",
+        ));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bytes_with_suffix() -> TestResult {
+    run(&[TEN, "-c", "1K"], "tests/expected/ten.txt.c200.out")?;
+    run(&[TEN, "-c", "1b"], "tests/expected/ten.txt.c200.out")
+}
+
 // --------------------------------------------------
 #[test]
 fn skips_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&[ONE, &bad, TWO])
+        .args([ONE, &bad, TWO])
         .assert()
         .stderr(predicate::str::is_match(expected)?);
 
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn skips_unreadable_file() -> TestResult {
+    let expected = format!("==> tests/inputs <==\n\n==> {} <==\n{}", ONE, fs::read_to_string(ONE)?);
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", ONE])
+        .assert()
+        .stdout(expected)
+        .stderr(predicate::str::contains("tests/inputs: Is a directory"));
+
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> TestResult {
-    // Extra work here due to lossy UTF
+    // Byte offsets may split multibyte characters, so compare raw bytes
     let mut file = File::open(expected_file)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    let expected = String::from_utf8_lossy(&buffer);
+    let mut expected = Vec::new();
+    file.read_to_end(&mut expected)?;
 
     Command::cargo_bin(PRG)?
         .args(args)
         .assert()
-        .stdout(predicate::eq(expected.as_bytes() as &[u8]));
+        .stdout(predicate::eq(expected.as_slice()));
 
     Ok(())
 }
@@ -829,3 +884,863 @@ fn multiple_files_c_plus_3() -> TestResult {
         "tests/expected/all.c+3.out",
     )
 }
+
+// --------------------------------------------------
+fn run_stdin(args: &[&str], input_file: &str, expected_file: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .pipe_stdin(input_file)?
+        .assert()
+        .success()
+        .stdout(fs::read(expected_file)?);
+
+    Ok(())
+}
+
+#[test]
+fn stdin_n3() -> TestResult {
+    run_stdin(&["-n", "3", "-"], TEN, "tests/expected/ten.txt.n3.out")
+}
+
+#[test]
+fn stdin_n0() -> TestResult {
+    run_stdin(&["-n", "0"], TEN, "tests/expected/ten.txt.n0.out")
+}
+
+#[test]
+fn stdin_n200() -> TestResult {
+    run_stdin(&["-n", "200"], TEN, "tests/expected/ten.txt.n200.out")
+}
+
+#[test]
+fn stdin_n_plus_2() -> TestResult {
+    run_stdin(&["-n", "+2"], TEN, "tests/expected/ten.txt.n+2.out")
+}
+
+#[test]
+fn stdin_n_plus_0() -> TestResult {
+    run_stdin(&["-n", "+0"], ONE, "tests/expected/one.txt.n+0.out")
+}
+
+#[test]
+fn stdin_c8() -> TestResult {
+    run_stdin(&["-c", "8", "-"], ONE, "tests/expected/one.txt.c8.out")
+}
+
+#[test]
+fn stdin_c200() -> TestResult {
+    run_stdin(&["-c", "200"], TEN, "tests/expected/ten.txt.c200.out")
+}
+
+#[test]
+fn stdin_c_plus_2() -> TestResult {
+    run_stdin(&["-c", "+2"], TEN, "tests/expected/ten.txt.c+2.out")
+}
+
+#[test]
+fn stdin_empty() -> TestResult {
+    run_stdin(&["-c", "3"], EMPTY, "tests/expected/empty.txt.c3.out")
+}
+
+#[test]
+fn stdin_multiple_files() -> TestResult {
+    let expected = format!(
+        "==> {} <==\n{}\n==> standard input <==\n{}",
+        ONE,
+        fs::read_to_string("tests/expected/one.txt.n1.out")?,
+        fs::read_to_string("tests/expected/two.txt.n1.out")?,
+    );
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", ONE, "-"])
+        .pipe_stdin(TWO)?
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+// Reads a named pipe that a writer thread fills with the given text
+fn run_fifo(args: &[&str], text: &'static str, expected: &str) -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("fifo");
+    assert!(StdCommand::new("mkfifo").arg(&path).status()?.success());
+
+    let fifo = path.clone();
+    let writer = thread::spawn(move || fs::write(fifo, text).unwrap());
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+
+    writer.join().unwrap();
+    Ok(())
+}
+
+#[test]
+fn fifo_lines() -> TestResult {
+    run_fifo(&["-n", "2"], "a\nb\nc\n", "b\nc\n")
+}
+
+#[test]
+fn fifo_bytes() -> TestResult {
+    run_fifo(&["-c", "2"], "a\nb\nc\n", "c\n")
+}
+
+#[test]
+fn fifo_follow_ends_with_input() -> TestResult {
+    run_fifo(&["-f", "-n", "1"], "one\ntwo\n", "two\n")
+}
+
+#[test]
+fn stdin_follow_ends_with_input() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-n", "1"])
+        .write_stdin("one\ntwo\n")
+        .assert()
+        .success()
+        .stdout("two\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_bytes(args: &[&str], expected: &[u8]) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(predicate::eq(expected));
+
+    Ok(())
+}
+
+#[test]
+fn zero_terminated_n2() -> TestResult {
+    run_bytes(&["-z", "-n", "2", ZERO], b"th\nree\0four")
+}
+
+#[test]
+fn zero_terminated_n_plus_2() -> TestResult {
+    run_bytes(&["--zero-terminated", "-n", "+2", ZERO], b"two\0th\nree\0four")
+}
+
+#[test]
+fn zero_terminated_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-n", "1"])
+        .write_stdin("a\0b\0")
+        .assert()
+        .success()
+        .stdout("b\0");
+
+    Ok(())
+}
+
+#[test]
+fn separator_n2() -> TestResult {
+    run_bytes(&["--separator", "e", "-n", "2", ZERO], b"e\0four")
+}
+
+#[test]
+fn separator_escape() -> TestResult {
+    run_bytes(&["--separator", "\\0", "-n", "1", ZERO], b"four")
+}
+
+#[test]
+fn dies_bad_separator() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--separator", "ab", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal separator -- ab"));
+
+    Ok(())
+}
+
+#[test]
+fn dies_zero_terminated_and_separator() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-z", "--separator", ";", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+#[test]
+fn binary_bytes() -> TestResult {
+    let input = fs::read(BINARY)?;
+    run_bytes(&["-c", "300", BINARY], &input[input.len() - 300..])?;
+    run_bytes(&["-c", "+200", BINARY], &input[199..])
+}
+
+#[test]
+fn binary_bytes_stdin() -> TestResult {
+    let input = fs::read(BINARY)?;
+    Command::cargo_bin(PRG)?
+        .args(["-c", "300"])
+        .pipe_stdin(BINARY)?
+        .assert()
+        .success()
+        .stdout(predicate::eq(&input[input.len() - 300..]));
+
+    Ok(())
+}
+
+#[test]
+fn verbose_single_file() -> TestResult {
+    let expected = format!(
+        "==> {} <==\n{}",
+        ONE,
+        fs::read_to_string("tests/expected/one.txt.out")?
+    );
+    Command::cargo_bin(PRG)?
+        .args(["-v", ONE])
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+#[test]
+fn verbose_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--verbose", "-n", "1"])
+        .write_stdin("one\ntwo\n")
+        .assert()
+        .success()
+        .stdout("==> standard input <==\ntwo\n");
+
+    Ok(())
+}
+
+#[test]
+fn last_of_quiet_and_verbose_wins() -> TestResult {
+    run(&["-v", "-q", "-n", "3", TEN, EMPTY, ONE, THREE, TWO], "tests/expected/all.n3.q.out")?;
+    run(&["-q", "-v", "-n", "3", TEN, EMPTY, ONE, THREE, TWO], "tests/expected/all.n3.out")
+}
+
+#[test]
+fn since_timestamp() -> TestResult {
+    run(
+        &["--since", "2024-01-31 10:00", APP_LOG],
+        "tests/expected/app.log.since1000.out",
+    )?;
+    run(
+        &["--since", "2024-01-31T10:00:01", APP_LOG],
+        "tests/expected/app.log.since100001.out",
+    )
+}
+
+#[test]
+fn since_timestamp_stdin() -> TestResult {
+    run_stdin(
+        &["--since", "2024-01-31 10:00"],
+        APP_LOG,
+        "tests/expected/app.log.since1000.out",
+    )
+}
+
+#[test]
+fn since_after_last_line() -> TestResult {
+    run_bytes(&["--since", "2024-02-01", APP_LOG], b"")
+}
+
+#[test]
+fn since_duration() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("recent.log");
+    let now = chrono::Local::now().naive_local();
+    let log: String = [120, 30, 10, 1]
+        .iter()
+        .map(|minutes| {
+            let time = now - chrono::TimeDelta::minutes(*minutes);
+            format!("{} {} minutes ago\n", time.format("%Y-%m-%d %H:%M:%S"), minutes)
+        })
+        .collect();
+    fs::write(&path, log)?;
+
+    Command::cargo_bin(PRG)?
+        .args(["--since", "15m", path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(
+            "^[^\n]* 10 minutes ago\n[^\n]* 1 minutes ago\n$",
+        )?);
+
+    Ok(())
+}
+
+#[test]
+fn since_timestamp_regex() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .env("TZ", "UTC")
+        .args([
+            "--since",
+            "2024-01-31 10:00",
+            "--timestamp-format",
+            r"\[([^\]]+)\]",
+            ACCESS_LOG,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("10.0.0.2 "))
+        .stdout(predicate::str::contains("GET /b"));
+
+    Ok(())
+}
+
+#[test]
+fn since_timestamp_strftime() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "--since",
+            "2024-01-31 10:10",
+            "--timestamp-format",
+            "%Y-%m-%d %H:%M:%S",
+            APP_LOG,
+        ])
+        .assert()
+        .success()
+        .stdout("2024-01-31 10:15:00 ERROR failed\n2024-01-31 11:00:00 INFO done\n");
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_since() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--since", "yesterday", APP_LOG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid duration or timestamp -- yesterday",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_since_and_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--since", "15m", "-n", "3", APP_LOG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+#[test]
+fn since_timestamp_strftime_without_year() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("syslog");
+    let now = chrono::Local::now().naive_local();
+    let log: String = [48, 2]
+        .iter()
+        .map(|hours| {
+            let time = now - chrono::TimeDelta::hours(*hours);
+            format!("{} host sshd: {} hours ago\n", time.format("%b %d %H:%M:%S"), hours)
+        })
+        .collect();
+    fs::write(&path, log)?;
+
+    Command::cargo_bin(PRG)?
+        .args(["--since", "1d", "--timestamp-format", "%b %d %H:%M:%S", path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("host sshd: 2 hours ago\n"))
+        .stdout(predicate::str::contains("48 hours").not());
+
+    Ok(())
+}
+
+#[test]
+fn dies_timestamp_format_without_date() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--since", "15m", "--timestamp-format", "%H:%M:%S", APP_LOG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid timestamp format -- %H:%M:%S"));
+
+    Ok(())
+}
+
+#[test]
+fn dies_timestamp_format_without_since() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--timestamp-format", "%Y-%m-%d %H:%M:%S", APP_LOG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("required arguments were not provided"));
+
+    Command::cargo_bin(PRG)?
+        .args(["--timestamp-format", "%Y-%m-%d %H:%M:%S", "-n", "1", APP_LOG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_timestamp_format() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--since", "15m", "--timestamp-format", "(", APP_LOG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid timestamp format -- ("));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn append(path: &Path, text: &str) {
+    let mut file = OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(text.as_bytes()).unwrap();
+}
+
+// --------------------------------------------------
+// Follows the files until a short-lived process exits while `change`
+// modifies them in the background
+fn follow(
+    args: &[&str],
+    change: impl FnOnce() + Send + 'static,
+) -> Result<assert_cmd::assert::Assert, Box<dyn std::error::Error>> {
+    let mut process = StdCommand::new("sleep").arg("1.5").spawn()?;
+    let pid = process.id().to_string();
+    let writer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        change();
+        // Reap the process, as a zombie still counts as running
+        process.wait().unwrap();
+    });
+
+    let assert = Command::cargo_bin(PRG)?
+        .args(["-s", "0.1", "--pid", &pid])
+        .args(args)
+        .assert();
+
+    writer.join().unwrap();
+    Ok(assert)
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_appended_lines() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\ntwo\n")?;
+
+    let appended = path.clone();
+    follow(&["-f", "-n", "1", path.to_str().unwrap()], move || {
+        append(&appended, "three\n");
+        append(&appended, "four");
+    })?
+    .success()
+    .stdout("two\nthree\nfour");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_offset_past_end() -> TestResult {
+    // Nothing is printed at first, and following starts at the end anyway
+    for offset in [["-c", "0"], ["-c", "+100"], ["-n", "0"], ["-n", "+100"]] {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("log.txt");
+        fs::write(&path, "one\ntwo\n")?;
+
+        let appended = path.clone();
+        follow(&["-f", offset[0], offset[1], path.to_str().unwrap()], move || {
+            append(&appended, "three\n");
+        })?
+        .success()
+        .stdout("three\n");
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_truncated_file() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\ntwo\n")?;
+
+    let truncated = path.clone();
+    follow(&["-f", path.to_str().unwrap()], move || {
+        fs::write(&truncated, "new\n").unwrap();
+    })?
+    .success()
+    .stdout("one\ntwo\nnew\n")
+    .stderr(predicate::str::contains("file truncated"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_name_after_rotation() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\n")?;
+
+    let rotated = path.clone();
+    follow(&["-F", path.to_str().unwrap()], move || {
+        append(&rotated, "two\n");
+        fs::rename(&rotated, rotated.with_extension("1")).unwrap();
+        fs::write(&rotated, "three\n").unwrap();
+    })?
+    .success()
+    .stdout("one\ntwo\nthree\n")
+    .stderr(predicate::str::contains("has been replaced"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_descriptor_after_rotation() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\n")?;
+
+    let rotated = path.clone();
+    follow(&["-f", path.to_str().unwrap()], move || {
+        let old = rotated.with_extension("1");
+        fs::rename(&rotated, &old).unwrap();
+        fs::write(&rotated, "new\n").unwrap();
+        append(&old, "two\n");
+    })?
+    .success()
+    .stdout("one\ntwo\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_name_missing_file() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+
+    let created = path.clone();
+    follow(&["-F", path.to_str().unwrap()], move || {
+        fs::write(&created, "one\n").unwrap();
+    })?
+    .success()
+    .stdout("one\n")
+    .stderr(predicate::str::contains("has appeared"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_multiple_files_headers() -> TestResult {
+    let dir = TempDir::new()?;
+    let first = dir.path().join("a.txt");
+    let second = dir.path().join("b.txt");
+    fs::write(&first, "a1\n")?;
+    fs::write(&second, "b1\n")?;
+    let (first_name, second_name) = (
+        first.to_str().unwrap().to_string(),
+        second.to_str().unwrap().to_string(),
+    );
+
+    let expected = format!(
+        "==> {0} <==\na1\n\n==> {1} <==\nb1\n\n==> {0} <==\na2\n",
+        first_name, second_name
+    );
+    follow(&["-f", &first_name, &second_name], move || {
+        append(&first, "a2\n");
+    })?
+    .success()
+    .stdout(expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_verbose_single_file() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\n")?;
+    let filename = path.to_str().unwrap().to_string();
+
+    let appended = path.clone();
+    follow(&["-f", "-v", &filename], move || {
+        append(&appended, "two\n");
+        append(&appended, "three\n");
+    })?
+    .success()
+    .stdout(format!("==> {} <==\none\ntwo\nthree\n", filename));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_quiet_multiple_files() -> TestResult {
+    let dir = TempDir::new()?;
+    let first = dir.path().join("a.txt");
+    let second = dir.path().join("b.txt");
+    fs::write(&first, "a1\n")?;
+    fs::write(&second, "b1\n")?;
+    let args = [
+        first.to_str().unwrap().to_string(),
+        second.to_str().unwrap().to_string(),
+    ];
+
+    follow(&["-f", "-q", &args[0], &args[1]], move || {
+        append(&second, "b2\n");
+        thread::sleep(Duration::from_millis(300));
+        append(&first, "a2\n");
+    })?
+    .success()
+    .stdout("a1\nb1\nb2\na2\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_polling_appended_lines() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\n")?;
+
+    let appended = path.clone();
+    follow(&["-f", "--polling", path.to_str().unwrap()], move || {
+        append(&appended, "two\n");
+    })?
+    .success()
+    .stdout("one\ntwo\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_polling_name_after_rotation() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\n")?;
+
+    let rotated = path.clone();
+    follow(&["-F", "--polling", path.to_str().unwrap()], move || {
+        fs::rename(&rotated, rotated.with_extension("1")).unwrap();
+        fs::write(&rotated, "two\n").unwrap();
+    })?
+    .success()
+    .stdout("one\ntwo\n")
+    .stderr(predicate::str::contains("has been replaced"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+// Appends to several files in turn and expects the output in the same
+// order, each block under the header of its file
+fn follow_output_ordering(backend_args: &[&str]) -> TestResult {
+    let dir = TempDir::new()?;
+    let paths: Vec<_> = ["a.log", "b.log", "c.log"]
+        .iter()
+        .map(|name| dir.path().join(name))
+        .collect();
+    for path in &paths {
+        fs::write(path, "")?;
+    }
+    let names: Vec<String> = paths
+        .iter()
+        .map(|path| path.to_str().unwrap().to_string())
+        .collect();
+
+    let mut args = vec!["-f"];
+    args.extend(backend_args);
+    args.extend(names.iter().map(String::as_str));
+
+    let appended = paths.clone();
+    let assert = follow(&args, move || {
+        for (idx, text) in [(1, "b1\n"), (0, "a1\n"), (2, "c1\n"), (0, "a2\n")] {
+            append(&appended[idx], text);
+            thread::sleep(Duration::from_millis(200));
+        }
+    })?;
+
+    let expected = format!(
+        "==> {0} <==\n\n==> {1} <==\n\n==> {2} <==\n\
+         \n==> {1} <==\nb1\n\n==> {0} <==\na1\n\
+         \n==> {2} <==\nc1\n\n==> {0} <==\na2\n",
+        names[0], names[1], names[2]
+    );
+    assert.success().stdout(expected);
+
+    Ok(())
+}
+
+#[test]
+fn follow_output_ordering_events() -> TestResult {
+    follow_output_ordering(&[])
+}
+
+#[test]
+fn follow_output_ordering_polling() -> TestResult {
+    follow_output_ordering(&["--polling"])
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_follow_no_files() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no files remaining"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_sleep_interval() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-s", "soon", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid number of seconds -- soon"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn match_counts_matching_lines() -> TestResult {
+    run_bytes(
+        &["--match", "WARN|ERROR", "-n", "1", APP_LOG],
+        b"2024-01-31 10:15:00 ERROR failed\n",
+    )?;
+    run_bytes(
+        &["--match", "WARN|ERROR", "-n", "2", APP_LOG],
+        b"2024-01-31 10:00:00 WARN slow request\n2024-01-31 10:15:00 ERROR failed\n",
+    )
+}
+
+#[test]
+fn match_from_start() -> TestResult {
+    run_bytes(
+        &["--match", "INFO", "-n", "+3", APP_LOG],
+        b"2024-01-31 10:00:00 INFO retrying\n2024-01-31 11:00:00 INFO done\n",
+    )
+}
+
+#[test]
+fn exclude_lines() -> TestResult {
+    run_bytes(
+        &["--exclude", "INFO|^ ", APP_LOG],
+        b"2024-01-31 10:00:00 WARN slow request\n2024-01-31 10:15:00 ERROR failed\n",
+    )
+}
+
+#[test]
+fn match_and_exclude() -> TestResult {
+    run_bytes(
+        &["--match", "^2024", "--exclude", "INFO", "-n", "1", APP_LOG],
+        b"2024-01-31 10:15:00 ERROR failed\n",
+    )
+}
+
+#[test]
+fn match_anchored_at_line_end() -> TestResult {
+    run_bytes(&["--match", "y$", APP_LOG], b"2024-01-31 09:30:00 INFO ready\n")?;
+    run_bytes(&["-z", "--match", "^t", ZERO], b"two\0th\nree\0")
+}
+
+#[test]
+fn match_stdin() -> TestResult {
+    let input = fs::read_to_string(APP_LOG)?;
+    Command::cargo_bin(PRG)?
+        .args(["--match", "ERROR|WARN", "-n", "1"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("2024-01-31 10:15:00 ERROR failed\n");
+
+    Ok(())
+}
+
+#[test]
+fn match_since() -> TestResult {
+    run_bytes(
+        &["--since", "2024-01-31 10:00", "--match", "INFO", APP_LOG],
+        b"2024-01-31 10:00:00 INFO retrying\n2024-01-31 11:00:00 INFO done\n",
+    )
+}
+
+#[test]
+fn follow_match() -> TestResult {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "INFO one\nERROR two\nINFO three\n")?;
+
+    let appended = path.clone();
+    follow(&["-f", "--match", "ERROR", path.to_str().unwrap()], move || {
+        append(&appended, "INFO four\nERROR fi");
+        thread::sleep(Duration::from_millis(300));
+        append(&appended, "ve\nINFO six\nERROR seven");
+    })?
+    .success()
+    .stdout("ERROR two\nERROR five\nERROR seven");
+
+    Ok(())
+}
+
+#[test]
+fn follow_match_spanning_blocks() -> TestResult {
+    // The matches are far apart, so finding them reads several blocks
+    let dir = TempDir::new()?;
+    let path = dir.path().join("log.txt");
+    let filler = "filler line\n".repeat(3000);
+    fs::write(&path, format!("MATCH first\n{}MATCH last\n", filler))?;
+
+    let appended = path.clone();
+    follow(&["-f", "-n", "2", "--match", "MATCH", path.to_str().unwrap()], move || {
+        append(&appended, "filler\nMATCH appended\n");
+    })?
+    .success()
+    .stdout("MATCH first\nMATCH last\nMATCH appended\n");
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_match() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--match", "(", APP_LOG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid regex -- ("));
+
+    Ok(())
+}
+
+#[test]
+fn dies_match_and_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--match", "INFO", "-c", "3", APP_LOG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}